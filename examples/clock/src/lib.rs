@@ -18,16 +18,6 @@ pub fn main() {
 
     let time = use_state(current_time());
 
-    let display = tp! {
-        p.text(time.get())
-         .style("font-size:4rem;font-family:monospace;letter-spacing:.1em;margin:1rem 0")
-    };
-    let disp_ref = display.clone();
-    let time_sub = time.clone();
-    time.subscribe(move || {
-        disp_ref.set_text_content(Some(&time_sub.get()));
-    });
-
     let time_tick = time.clone();
     use_interval(
         move || {
@@ -43,8 +33,9 @@ pub fn main() {
             h1.text("🕐 Typhoon Clock").style("margin-bottom:.5rem")
             p.text("Built with use_interval — no JavaScript")
              .style("color:#6c7086;font-size:.9rem;margin-bottom:1.5rem")
+            p.text(time)
+             .style("font-size:4rem;font-family:monospace;letter-spacing:.1em;margin:1rem 0")
         }
     };
-    app.append_child(display.as_ref()).unwrap();
     mount(app);
 }
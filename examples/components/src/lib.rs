@@ -29,16 +29,8 @@ fn card(title: &str, body: &str, tag: &str) -> Element {
 fn mini_counter(label: &str) -> Element {
     let count = use_state(0i32);
 
-    let display = tp! {
-        span.style("font-family:monospace;min-width:80px;display:inline-block;text-align:center")
-    };
-    let disp_ref = display.clone();
-    let count_sub = count.clone();
     let lbl = label.to_string();
-    display.set_text_content(Some(&format!("{}: 0", lbl)));
-    count.subscribe(move || {
-        disp_ref.set_text_content(Some(&format!("{}: {}", lbl, count_sub.get())));
-    });
+    let count_for_label = count.clone();
 
     let count_inc = count.clone();
     let count_dec = count.clone();
@@ -58,6 +50,11 @@ fn mini_counter(label: &str) -> Element {
         div.style("display:flex;align-items:center;gap:.6rem;padding:.5rem 0;\
                    border-bottom:1px solid #313244")
     };
+    let display = tp! {
+        span.text(move || format!("{}: {}", lbl, count_for_label.get()))
+            .style("font-family:monospace;min-width:80px;display:inline-block;text-align:center")
+    };
+
     row.append_child(dec.as_ref()).unwrap();
     row.append_child(display.as_ref()).unwrap();
     row.append_child(inc.as_ref()).unwrap();
@@ -93,7 +90,7 @@ pub fn main() {
         .unwrap();
     grid.append_child(card("Clock", "Ticking timer", "use_interval").as_ref())
         .unwrap();
-    grid.append_child(card("Router", "Hash-based navigation", "use_router").as_ref())
+    grid.append_child(card("Router", "Hash & History API navigation", "use_routes").as_ref())
         .unwrap();
 
     app.append_child(grid.as_ref()).unwrap();
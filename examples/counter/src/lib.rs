@@ -7,13 +7,6 @@ pub fn main() {
 
     let count = use_state(0i32);
 
-    let display = tp! { p.text(count.get()).style("font-size:2rem;margin:1rem 0") };
-    let display_ref = display.clone();
-    let count_sub = count.clone();
-    count.subscribe(move || {
-        display_ref.set_text_content(Some(&count_sub.get().to_string()));
-    });
-
     let count_inc = count.clone();
     let on_inc = move || count_inc.set(count_inc.get() + 1);
 
@@ -31,9 +24,12 @@ pub fn main() {
                 button.onclick(on_rst).style("font-size:1rem;padding:.5rem 1rem;cursor:pointer") { "Reset" }
                 button.onclick(on_inc).style("font-size:1.5rem;padding:.5rem 1.2rem;cursor:pointer") { "+" }
             }
+            p.text(count).style("font-size:2rem;margin:1rem 0")
+            if count.get() < 0 {
+                p.text("Below zero!").style("color:#e64553")
+            }
         }
     };
 
-    app.append_child(display.as_ref()).unwrap();
     mount(app);
 }
@@ -1,41 +1,70 @@
+use serde::{Deserialize, Serialize};
 use typhoon_core::prelude::*;
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Todo {
+    id: u32,
+    text: String,
+}
+
+/// Todos used to be saved as plain strings. Rewrite any such entries as
+/// `Todo`s, numbered after the highest existing id, so the list loads.
+fn migrate_saved_todos() {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Todo(Todo),
+        Text(String),
+    }
+
+    let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) else {
+        return;
+    };
+    let Some(saved) = storage
+        .get_item("todos")
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str::<Vec<Saved>>(&json).ok())
+    else {
+        return;
+    };
+    if saved.iter().all(|item| matches!(item, Saved::Todo(_))) {
+        return;
+    }
+
+    let mut next_id = saved
+        .iter()
+        .filter_map(|item| match item {
+            Saved::Todo(todo) => Some(todo.id + 1),
+            Saved::Text(_) => None,
+        })
+        .max()
+        .unwrap_or(0);
+    let todos: Vec<Todo> = saved
+        .into_iter()
+        .map(|item| match item {
+            Saved::Todo(todo) => todo,
+            Saved::Text(text) => {
+                let id = next_id;
+                next_id += 1;
+                Todo { id, text }
+            }
+        })
+        .collect();
+    if let Ok(json) = serde_json::to_string(&todos) {
+        storage.set_item("todos", &json).ok();
+    }
+}
 
 #[wasm_bindgen(start)]
 pub fn main() {
     init();
 
-    let todos: Signal<Vec<String>> = use_local_storage("todos", vec![]);
+    migrate_saved_todos();
+    let todos: Signal<Vec<Todo>> = use_local_storage("todos", vec![]);
     let input_val: Signal<String> = use_state(String::new());
 
-    let list = tp! { ul.style("list-style:none;padding:0;margin:1rem 0;max-width:400px") };
-    let list_ref = list.clone();
-
-    let todos_for_sub = todos.clone();
-    todos.subscribe(move || {
-        while let Some(child) = list_ref.first_child() {
-            list_ref.remove_child(&child).ok();
-        }
-        for (i, item) in todos_for_sub.get().iter().enumerate() {
-            let li = tp! {
-                li.style("display:flex;align-items:center;gap:.5rem;padding:.4rem 0;border-bottom:1px solid #313244")
-            };
-            let span = tp! { span.text(item) };
-            let todos_del = todos_for_sub.clone();
-            let idx = i;
-            let del_btn = tp! { button.onclick(move || {
-                let mut v = todos_del.get();
-                v.remove(idx);
-                todos_del.set(v);
-            }).style("margin-left:auto;cursor:pointer;background:#313244;color:#f38ba8;border:none;border-radius:4px;padding:2px 8px") };
-            del_btn.set_text_content(Some("✕"));
-            li.append_child(span.as_ref()).unwrap();
-            li.append_child(del_btn.as_ref()).unwrap();
-            list_ref.append_child(li.as_ref()).unwrap();
-        }
-    });
-
     let add_todo = {
         let todos = todos.clone();
         let input_val = input_val.clone();
@@ -44,7 +73,8 @@ pub fn main() {
             let trimmed = val.trim().to_string();
             if !trimmed.is_empty() {
                 let mut v = todos.get();
-                v.push(trimmed);
+                let id = v.iter().map(|t| t.id + 1).max().unwrap_or(0);
+                v.push(Todo { id, text: trimmed });
                 todos.set(v);
                 input_val.set(String::new());
             }
@@ -57,20 +87,12 @@ pub fn main() {
     let inp = tp! {
         input
             .placeholder(&"Add a task…")
+            .value(input_val)
             .style("flex:1;padding:.5rem .8rem;background:#1e1e2e;color:#cdd6f4;border:1px solid #45475a;border-radius:6px;font-size:1rem")
             .oninput(move |v: String| input_val_for_input.set(v))
             .onkeydown(move |key: String| { if key == "Enter" { add_for_input(); } })
     };
 
-    // Sync the DOM value property so the field clears after adding
-    let inp_ref = inp.clone();
-    let input_val_sub = input_val.clone();
-    input_val.subscribe(move || {
-        inp_ref.set_attribute("value", &input_val_sub.get()).ok();
-        let html_input: web_sys::HtmlInputElement = inp_ref.clone().dyn_into().unwrap();
-        html_input.set_value(&input_val_sub.get());
-    });
-
     let app = tp! {
         div.class("app").style("font-family:sans-serif;padding:2rem;max-width:480px;margin:0 auto") {
             h1.text("🌀 Typhoon Todo").style("margin-bottom:1rem")
@@ -89,6 +111,25 @@ pub fn main() {
     row.append_child(inp.as_ref()).unwrap();
     row.append_child(add_btn.as_ref()).unwrap();
     app.append_child(row.as_ref()).unwrap();
+
+    let todos_for_rows = todos.clone();
+    let list = tp! {
+        ul.style("list-style:none;padding:0;margin:1rem 0;max-width:400px") {
+            for todo in todos key = todo.id {
+                li.style("display:flex;align-items:center;gap:.5rem;padding:.4rem 0;border-bottom:1px solid #313244") {
+                    span.text(&todo.text)
+                    button.onclick({
+                        let todos = todos_for_rows.clone();
+                        move || {
+                            let mut v = todos.get();
+                            v.retain(|t| t.id != todo.id);
+                            todos.set(v);
+                        }
+                    }).style("margin-left:auto;cursor:pointer;background:#313244;color:#f38ba8;border:none;border-radius:4px;padding:2px 8px") { "✕" }
+                }
+            }
+        }
+    };
     app.append_child(list.as_ref()).unwrap();
 
     mount(app);
@@ -0,0 +1,51 @@
+//! Value bindings behind `tp!`'s `.text`, `.class`, `.style`, `.value` and attributes.
+//!
+//! The macro expands `.text(arg)` to `(&arg).__bind(&el, apply)` with both
+//! traits in scope. Method resolution tries the receiver by value before
+//! auto-referencing it, so a `Signal<T>` or a closure picks [`BindSignal`]
+//! (reactive) and anything else that is `Display` falls through to
+//! [`BindValue`] (set once).
+
+use std::fmt::Display;
+
+use web_sys::Element;
+
+use crate::{Signal, create_effect};
+
+/// Binds a reactive value: applies it now and again on every change.
+pub trait BindSignal {
+    fn __bind<A: Fn(&Element, &str) + 'static>(&self, el: &Element, apply: A);
+}
+
+impl<T: Clone + Display + 'static> BindSignal for Signal<T> {
+    fn __bind<A: Fn(&Element, &str) + 'static>(&self, el: &Element, apply: A) {
+        let el = el.clone();
+        let signal = self.clone();
+        create_effect(move || apply(&el, &signal.get().to_string()));
+    }
+}
+
+/// `.text(move || format!("{} left", count.get()))` — re-evaluated whenever a
+/// signal read inside the closure changes.
+impl<F, R> BindSignal for F
+where
+    F: Fn() -> R + Clone + 'static,
+    R: Display,
+{
+    fn __bind<A: Fn(&Element, &str) + 'static>(&self, el: &Element, apply: A) {
+        let el = el.clone();
+        let f = self.clone();
+        create_effect(move || apply(&el, &f().to_string()));
+    }
+}
+
+/// Binds a plain value: applies it once.
+pub trait BindValue {
+    fn __bind<A: Fn(&Element, &str) + 'static>(&self, el: &Element, apply: A);
+}
+
+impl<T: Display + ?Sized> BindValue for &T {
+    fn __bind<A: Fn(&Element, &str) + 'static>(&self, el: &Element, apply: A) {
+        apply(el, &self.to_string());
+    }
+}
@@ -0,0 +1,100 @@
+//! DOM event listeners that are removed again when their scope goes away.
+//!
+//! Listeners kept outside any scope are left to the JS garbage collector:
+//! the handler is freed together with the element it listens on.
+
+use wasm_bindgen::{JsCast, closure::Closure};
+use web_sys::{Element, EventTarget};
+
+use crate::reactive::{has_owner, on_cleanup};
+
+/// Handle to a listener added with [`listen`]. Removes the listener on drop.
+#[must_use = "the listener is removed as soon as the EventListener is dropped; call .forget() to keep it"]
+pub struct EventListener {
+    inner: Option<Listener>,
+}
+
+struct Listener {
+    target: EventTarget,
+    event: String,
+    closure: Closure<dyn FnMut(web_sys::Event)>,
+}
+
+impl Listener {
+    fn remove(self) {
+        self.target
+            .remove_event_listener_with_callback(&self.event, self.closure.as_ref().unchecked_ref())
+            .ok();
+    }
+}
+
+impl EventListener {
+    /// Keeps the listener until the current [`Scope`](crate::Scope) is
+    /// disposed. Outside any scope it stays for as long as its target does:
+    /// the handler is handed to the JS garbage collector, which frees it
+    /// once the target is gone (in engines with weak references, which
+    /// covers current browsers).
+    pub fn forget(mut self) {
+        let Some(listener) = self.inner.take() else {
+            return;
+        };
+        if has_owner() {
+            on_cleanup(move || listener.remove());
+        } else {
+            // The target keeps the JS function alive, and the function the
+            // Rust closure.
+            let _ = listener.closure.into_js_value();
+        }
+    }
+}
+
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        if let Some(listener) = self.inner.take() {
+            listener.remove();
+        }
+    }
+}
+
+/// Listens for `event` on `target`, passing the event as `E`.
+///
+/// `E` is not checked: pick the type the browser dispatches for `event`.
+///
+/// ```ignore
+/// let _resize = listen(&window, "resize", move |_: web_sys::UiEvent| relayout());
+/// ```
+pub fn listen<E, F>(target: &EventTarget, event: &str, mut handler: F) -> EventListener
+where
+    E: JsCast,
+    F: FnMut(E) + 'static,
+{
+    let closure = Closure::<dyn FnMut(_)>::new(move |event: web_sys::Event| {
+        handler(event.unchecked_into());
+    });
+    target
+        .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())
+        .unwrap_or_else(|_| panic!("failed to add {event} listener"));
+    EventListener {
+        inner: Some(Listener {
+            target: target.clone(),
+            event: event.to_owned(),
+            closure,
+        }),
+    }
+}
+
+/// Listens for any DOM `event` on `el` for as long as the current
+/// [`Scope`](crate::Scope) lives — `tp!`'s `.on(..)` and `.onclick(..)` go
+/// through here, so handlers of a swapped-out view are removed with it.
+/// Outside any scope the handler lives as long as `el` does.
+///
+/// ```ignore
+/// on(&form, "submit", |e: web_sys::SubmitEvent| e.prevent_default());
+/// ```
+pub fn on<E, F>(el: &Element, event: &str, handler: F)
+where
+    E: JsCast,
+    F: FnMut(E) + 'static,
+{
+    listen(el, event, handler).forget();
+}
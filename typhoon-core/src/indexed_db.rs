@@ -0,0 +1,573 @@
+//! IndexedDB object stores as reactive collections.
+//!
+//! Values are serde types, stored as plain JS objects (converted through
+//! JSON) so that key paths and indexes can see their fields. The store is
+//! loaded with `spawn_local`, and writes update the collection straight away
+//! and reach the database in the background.
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    rc::Rc,
+};
+
+use js_sys::{JSON, Promise};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use wasm_bindgen::{JsCast, JsValue, prelude::Closure};
+use wasm_bindgen_futures::{JsFuture, future_to_promise};
+use web_sys::{
+    IdbDatabase, IdbObjectStore, IdbObjectStoreParameters, IdbOpenDbRequest, IdbRequest,
+    IdbTransactionMode,
+};
+
+use crate::{Signal, error_message, spawn_local, untrack};
+
+/// Which object store [`use_indexed_db`] opens, and how it is laid out.
+///
+/// ```ignore
+/// IndexedDb::new("app", "drafts")
+///     .key_path("id")
+///     .index("by_author", "author")
+/// ```
+#[derive(Clone, Debug)]
+pub struct IndexedDb {
+    database: String,
+    store: String,
+    version: u32,
+    key_path: String,
+    indexes: Vec<(String, String)>,
+}
+
+impl IndexedDb {
+    /// The object store `store` in the database `database`, keyed by each
+    /// value's `id` field.
+    pub fn new(database: &str, store: &str) -> Self {
+        IndexedDb {
+            database: database.to_owned(),
+            store: store.to_owned(),
+            version: 1,
+            key_path: "id".to_owned(),
+            indexes: Vec::new(),
+        }
+    }
+
+    /// The field holding each value's key; `"meta.id"` reaches into nested
+    /// objects. Fixed once the store has been created.
+    pub fn key_path(mut self, key_path: &str) -> Self {
+        self.key_path = key_path.to_owned();
+        self
+    }
+
+    /// Adds an index called `name` over the field `key_path`, for
+    /// [`DbCollection::query`].
+    pub fn index(mut self, name: &str, key_path: &str) -> Self {
+        self.indexes.push((name.to_owned(), key_path.to_owned()));
+        self
+    }
+
+    /// The database version (default 1). Missing stores and indexes are only
+    /// created when the version goes up, so bump it whenever you add one.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+/// A failed IndexedDB operation, or a value that couldn't be converted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbError(String);
+
+impl DbError {
+    pub fn message(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "IndexedDB: {}", self.0)
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<JsValue> for DbError {
+    fn from(err: JsValue) -> Self {
+        DbError(error_message(&err))
+    }
+}
+
+impl From<serde_json::Error> for DbError {
+    fn from(err: serde_json::Error) -> Self {
+        DbError(err.to_string())
+    }
+}
+
+/// An object store, kept in memory as a reactive list. See [`use_indexed_db`].
+#[derive(Clone)]
+pub struct DbCollection<T: Clone + 'static> {
+    /// Resolves to the open `IdbDatabase`.
+    db: Promise,
+    store: Rc<str>,
+    key_path: Rc<str>,
+    items: Signal<Vec<T>>,
+    loading: Signal<bool>,
+    error: Signal<Option<DbError>>,
+    /// Local changes the database hasn't confirmed yet, by id. Re-applied
+    /// over every load, which may have read the store before they landed.
+    pending: Rc<RefCell<Unconfirmed<T>>>,
+    next_change: Rc<Cell<u64>>,
+    /// Id of the latest reload. Older ones still in flight are ignored.
+    latest_load: Rc<Cell<u64>>,
+}
+
+type Unconfirmed<T> = Vec<(u64, Change<T>)>;
+
+/// A `put` or `delete` applied to `items` ahead of the database.
+#[derive(Clone)]
+enum Change<T> {
+    Put(T),
+    Delete(Value),
+}
+
+impl<T: Serialize + Clone> Change<T> {
+    fn apply(&self, items: &mut Vec<T>, key_path: &str) {
+        match self {
+            Change::Put(value) => upsert(items, key_path, value.clone()),
+            Change::Delete(key) => remove(items, key_path, key),
+        }
+    }
+}
+
+/// Opens an IndexedDB object store as a reactive collection of `T`.
+///
+/// The database is opened (and the store and indexes created) in the
+/// background; [`loading`](DbCollection::loading) is `true` until the
+/// store's contents are in [`items`](DbCollection::items). `put` and
+/// `delete` update `items` immediately and write in the background; until a
+/// write lands, it is kept on top of whatever a load reads. If a write
+/// fails, the error lands in [`error`](DbCollection::error) and `items` is
+/// reloaded from the database.
+///
+/// ```ignore
+/// #[derive(Clone, Serialize, Deserialize)]
+/// struct Draft { id: u32, author: String, body: String }
+///
+/// let drafts = use_indexed_db::<Draft>(IndexedDb::new("app", "drafts").index("by_author", "author"));
+/// let items = drafts.items();
+/// tp! {
+///     if drafts.loading().get() { p { "Loading…" } }
+///     ul { for d in items key = d.id { li.text(&d.body) } }
+/// }
+/// drafts.put(Draft { id: 1, author: "ana".into(), body: "Hello".into() });
+/// ```
+pub fn use_indexed_db<T>(db: IndexedDb) -> DbCollection<T>
+where
+    T: Serialize + DeserializeOwned + Clone + 'static,
+{
+    let collection = DbCollection {
+        db: open(&db),
+        store: db.store.into(),
+        key_path: db.key_path.into(),
+        items: Signal::new(Vec::new()),
+        loading: Signal::new(true),
+        error: Signal::new(None),
+        pending: Rc::default(),
+        next_change: Rc::default(),
+        latest_load: Rc::default(),
+    };
+    collection.reload();
+    collection
+}
+
+impl<T> DbCollection<T>
+where
+    T: Serialize + DeserializeOwned + Clone + 'static,
+{
+    /// Every value in the store, in key order as loaded.
+    pub fn items(&self) -> Signal<Vec<T>> {
+        self.items.clone()
+    }
+
+    /// `true` while the store's contents are being (re)loaded.
+    pub fn loading(&self) -> Signal<bool> {
+        self.loading.clone()
+    }
+
+    /// The last error, if any.
+    pub fn error(&self) -> Signal<Option<DbError>> {
+        self.error.clone()
+    }
+
+    /// Reads one value by key straight from the database.
+    pub async fn get<K: Serialize>(&self, key: &K) -> Result<Option<T>, DbError> {
+        let key = to_js(key)?;
+        let store = self.object_store(IdbTransactionMode::Readonly).await?;
+        let value = settle(&store.get(&key)?).await?;
+        if value.is_undefined() {
+            return Ok(None);
+        }
+        from_js(&value).map(Some)
+    }
+
+    /// Every value whose `index` field equals `value`.
+    ///
+    /// ```ignore
+    /// let mine = drafts.query("by_author", &"ana").await?;
+    /// ```
+    pub async fn query<K: Serialize>(&self, index: &str, value: &K) -> Result<Vec<T>, DbError> {
+        let value = to_js(value)?;
+        let store = self.object_store(IdbTransactionMode::Readonly).await?;
+        let found = settle(&store.index(index)?.get_all_with_key(&value)?).await?;
+        from_js(&found)
+    }
+
+    /// Adds `value`, or replaces the value with the same key.
+    pub fn put(&self, value: T) {
+        let change = self.begin(Change::Put(value.clone()));
+        let this = self.clone();
+        spawn_local(async move {
+            let written = async {
+                let value = to_js(&value)?;
+                let store = this.object_store(IdbTransactionMode::Readwrite).await?;
+                settle(&store.put(&value)?).await
+            };
+            this.finish(change, written.await);
+        });
+    }
+
+    /// Removes the value with the given key, if there is one.
+    pub fn delete<K: Serialize>(&self, key: &K) {
+        let key = match serde_json::to_value(key) {
+            Ok(key) => key,
+            Err(err) => return self.error.set(Some(err.into())),
+        };
+        let change = self.begin(Change::Delete(key.clone()));
+        let this = self.clone();
+        spawn_local(async move {
+            let deleted = async {
+                let key = to_js(&key)?;
+                let store = this.object_store(IdbTransactionMode::Readwrite).await?;
+                settle(&store.delete(&key)?).await
+            };
+            this.finish(change, deleted.await);
+        });
+    }
+
+    /// Re-reads the whole store into [`items`](DbCollection::items).
+    pub fn reload(&self) {
+        let load = self.start_load();
+        let this = self.clone();
+        spawn_local(async move {
+            let loaded = async {
+                let store = this.object_store(IdbTransactionMode::Readonly).await?;
+                from_js(&settle(&store.get_all()?).await?)
+            };
+            this.finish_load(load, loaded.await);
+        });
+    }
+
+    /// Marks the collection as loading; returns the new load's id.
+    fn start_load(&self) -> u64 {
+        let load = self.latest_load.get() + 1;
+        self.latest_load.set(load);
+        self.loading.set(true);
+        load
+    }
+
+    /// Load `load` is done. Unless a newer one has started since, its result
+    /// replaces `items` and loading ends.
+    fn finish_load(&self, load: u64, loaded: Result<Vec<T>, DbError>) {
+        if load != self.latest_load.get() {
+            return;
+        }
+        match loaded {
+            Ok(items) => {
+                let items = overlay(items, &self.key_path, &self.pending.borrow());
+                self.items.set(items);
+            }
+            Err(err) => self.error.set(Some(err)),
+        }
+        self.loading.set(false);
+    }
+
+    /// Applies `change` to `items` and holds on to it until the database
+    /// confirms it; returns its id.
+    fn begin(&self, change: Change<T>) -> u64 {
+        let mut items = untrack(|| self.items.get());
+        change.apply(&mut items, &self.key_path);
+        let id = self.next_change.get();
+        self.next_change.set(id + 1);
+        self.pending.borrow_mut().push((id, change));
+        self.items.set(items);
+        id
+    }
+
+    /// The write for change `id` is done. If it didn't make it, report it and
+    /// resync with the database.
+    fn finish(&self, id: u64, written: Result<JsValue, DbError>) {
+        self.pending
+            .borrow_mut()
+            .retain(|(change, _)| *change != id);
+        if let Err(err) = written {
+            self.error.set(Some(err));
+            self.reload();
+        }
+    }
+
+    async fn object_store(&self, mode: IdbTransactionMode) -> Result<IdbObjectStore, DbError> {
+        let db: IdbDatabase = JsFuture::from(self.db.clone()).await?.unchecked_into();
+        let transaction = db.transaction_with_str_and_mode(&self.store, mode)?;
+        Ok(transaction.object_store(&self.store)?)
+    }
+}
+
+/// Opens the database, creating the store and indexes on an upgrade.
+fn open(config: &IndexedDb) -> Promise {
+    let request = web_sys::window()
+        .expect("no window")
+        .indexed_db()
+        .ok()
+        .flatten()
+        .ok_or_else(|| JsValue::from_str("IndexedDB is unavailable"))
+        .and_then(|factory| factory.open_with_u32(&config.database, config.version));
+    let request = match request {
+        Ok(request) => request,
+        Err(err) => return Promise::reject(&err),
+    };
+
+    let config = config.clone();
+    let upgrading = request.clone();
+    let on_upgrade = Closure::<dyn FnMut()>::new(move || {
+        if upgrade(&upgrading, &config).is_err()
+            && let Some(transaction) = upgrading.transaction()
+        {
+            // Fails the open, which reports the error.
+            transaction.abort().ok();
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+
+    future_to_promise(async move {
+        let db = settle(&request).await;
+        // `upgradeneeded` fires before the open succeeds or fails.
+        drop(on_upgrade);
+        db.map_err(|err| JsValue::from_str(err.message()))
+    })
+}
+
+fn upgrade(request: &IdbOpenDbRequest, config: &IndexedDb) -> Result<(), JsValue> {
+    let db: IdbDatabase = request.result()?.unchecked_into();
+    let store = if db.object_store_names().contains(&config.store) {
+        request
+            .transaction()
+            .ok_or_else(|| JsValue::from_str("no upgrade transaction"))?
+            .object_store(&config.store)?
+    } else {
+        let params = IdbObjectStoreParameters::new();
+        params.set_key_path(&JsValue::from_str(&config.key_path));
+        db.create_object_store_with_optional_parameters(&config.store, &params)?
+    };
+    for (name, key_path) in &config.indexes {
+        if !store.index_names().contains(name) {
+            store.create_index_with_str(name, key_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Waits for `request` to succeed or fail.
+async fn settle(request: &IdbRequest) -> Result<JsValue, DbError> {
+    let mut settle = None;
+    let promise = Promise::new(&mut |resolve, reject| settle = Some((resolve, reject)));
+    let (resolve, reject) = settle.expect("Promise::new runs its executor immediately");
+
+    let succeeded = request.clone();
+    let on_success = Closure::<dyn FnMut()>::new(move || {
+        let result = succeeded.result().unwrap_or_default();
+        resolve.call1(&JsValue::NULL, &result).ok();
+    });
+    let failed = request.clone();
+    let on_error = Closure::<dyn FnMut()>::new(move || {
+        let error = failed.error().ok().flatten().map(JsValue::from);
+        reject
+            .call1(&JsValue::NULL, &error.unwrap_or(JsValue::NULL))
+            .ok();
+    });
+    request.set_onsuccess(Some(on_success.as_ref().unchecked_ref()));
+    request.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+    let result = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    Ok(result?)
+}
+
+fn to_js<V: Serialize>(value: &V) -> Result<JsValue, DbError> {
+    Ok(JSON::parse(&serde_json::to_string(value)?)?)
+}
+
+fn from_js<T: DeserializeOwned>(value: &JsValue) -> Result<T, DbError> {
+    let json = JSON::stringify(value)?
+        .as_string()
+        .ok_or_else(|| DbError("value is not JSON".to_owned()))?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// The value at a dotted `key_path` inside `value`.
+fn key_of<'a>(value: &'a Value, key_path: &str) -> Option<&'a Value> {
+    key_path
+        .split('.')
+        .try_fold(value, |value, field| value.get(field))
+}
+
+fn item_key<T: Serialize>(item: &T, key_path: &str) -> Option<Value> {
+    key_of(&serde_json::to_value(item).ok()?, key_path).cloned()
+}
+
+/// Replaces the item with `value`'s key, or appends `value`.
+fn upsert<T: Serialize>(items: &mut Vec<T>, key_path: &str, value: T) {
+    let key = item_key(&value, key_path);
+    let existing = key.as_ref().and_then(|key| {
+        items
+            .iter()
+            .position(|item| item_key(item, key_path).as_ref() == Some(key))
+    });
+    match existing {
+        Some(i) => items[i] = value,
+        None => items.push(value),
+    }
+}
+
+/// Removes the item with key `key`.
+fn remove<T: Serialize>(items: &mut Vec<T>, key_path: &str, key: &Value) {
+    items.retain(|item| item_key(item, key_path).as_ref() != Some(key));
+}
+
+/// `loaded` with the unconfirmed `pending` changes applied in order.
+fn overlay<T: Serialize + Clone>(
+    mut loaded: Vec<T>,
+    key_path: &str,
+    pending: &[(u64, Change<T>)],
+) -> Vec<T> {
+    for (_, change) in pending {
+        change.apply(&mut loaded, key_path);
+    }
+    loaded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Draft {
+        id: u32,
+        body: String,
+    }
+
+    fn draft(id: u32, body: &str) -> Draft {
+        Draft {
+            id,
+            body: body.into(),
+        }
+    }
+
+    #[test]
+    fn keys_are_read_from_dotted_paths() {
+        let value = json!({ "id": 1, "meta": { "slug": "a" } });
+        assert_eq!(key_of(&value, "id"), Some(&json!(1)));
+        assert_eq!(key_of(&value, "meta.slug"), Some(&json!("a")));
+        assert_eq!(key_of(&value, "meta.missing"), None);
+        assert_eq!(key_of(&json!(3), "id"), None);
+    }
+
+    #[test]
+    fn puts_replace_by_key_or_append() {
+        let mut items = vec![draft(1, "a"), draft(2, "b")];
+        upsert(&mut items, "id", draft(2, "B"));
+        assert_eq!(items, [draft(1, "a"), draft(2, "B")]);
+        upsert(&mut items, "id", draft(7, "c"));
+        assert_eq!(items, [draft(1, "a"), draft(2, "B"), draft(7, "c")]);
+    }
+
+    #[test]
+    fn deletes_remove_by_key() {
+        let mut items = vec![draft(1, "a"), draft(2, "b")];
+        remove(&mut items, "id", &json!(1));
+        assert_eq!(items, [draft(2, "b")]);
+        remove(&mut items, "id", &json!("2"));
+        assert_eq!(items, [draft(2, "b")]);
+    }
+
+    #[test]
+    fn put_before_initial_load_survives_the_load() {
+        // `put` ran while the first `getAll` was in flight, so the load
+        // didn't see it.
+        let loaded = vec![draft(1, "a"), draft(2, "b")];
+        let pending = [
+            (0, Change::Put(draft(3, "new"))),
+            (1, Change::Put(draft(1, "A"))),
+            (2, Change::Delete(json!(2))),
+        ];
+        assert_eq!(
+            overlay(loaded, "id", &pending),
+            [draft(1, "A"), draft(3, "new")]
+        );
+    }
+
+    /// A collection with no database behind it, for driving loads by hand.
+    fn detached() -> DbCollection<Draft> {
+        DbCollection {
+            db: JsValue::NULL.unchecked_into(),
+            store: "drafts".into(),
+            key_path: "id".into(),
+            items: Signal::new(Vec::new()),
+            loading: Signal::new(true),
+            error: Signal::new(None),
+            pending: Rc::default(),
+            next_change: Rc::default(),
+            latest_load: Rc::default(),
+        }
+    }
+
+    #[test]
+    fn only_the_latest_load_settles_the_collection() {
+        let drafts = detached();
+        let first = drafts.start_load();
+        let second = drafts.start_load();
+
+        // The first load finishing doesn't end the second one.
+        drafts.finish_load(first, Ok(vec![draft(1, "old")]));
+        assert!(drafts.loading().get());
+        assert!(drafts.items().get().is_empty());
+
+        drafts.finish_load(second, Ok(vec![draft(1, "new")]));
+        assert!(!drafts.loading().get());
+        assert_eq!(drafts.items().get(), [draft(1, "new")]);
+
+        // A stale result arriving late doesn't overwrite a newer one.
+        let third = drafts.start_load();
+        let fourth = drafts.start_load();
+        drafts.finish_load(fourth, Ok(vec![draft(2, "newest")]));
+        drafts.finish_load(third, Err(DbError("aborted".into())));
+        assert_eq!(drafts.items().get(), [draft(2, "newest")]);
+        assert_eq!(drafts.error().get(), None);
+    }
+
+    #[test]
+    fn confirmed_changes_apply_cleanly_twice() {
+        // A load can also start after a write landed but before it's
+        // confirmed; re-applying it must not duplicate anything.
+        let loaded = vec![draft(1, "a"), draft(3, "new")];
+        let pending = [(0, Change::Put(draft(3, "new")))];
+        assert_eq!(
+            overlay(loaded, "id", &pending),
+            [draft(1, "a"), draft(3, "new")]
+        );
+    }
+}
@@ -2,11 +2,48 @@
 
 use std::{cell::RefCell, rc::Rc};
 
-use serde::{Serialize, de::DeserializeOwned};
 use wasm_bindgen::prelude::*;
-use web_sys::{Document, Element, Text};
-
-pub use typhoon_macro::tp;
+use web_sys::{Document, DocumentFragment, Element, Node, Text};
+
+pub use serde_json;
+pub use typhoon_macro::{Routable, tp};
+pub use web_sys;
+
+mod bind;
+mod event;
+mod indexed_db;
+mod list;
+mod query;
+mod reactive;
+mod router;
+mod scroll;
+mod storage;
+mod switch;
+
+#[doc(hidden)]
+pub use bind::{BindSignal, BindValue};
+pub use event::{EventListener, listen, on};
+pub use indexed_db::{DbCollection, DbError, IndexedDb, use_indexed_db};
+pub use list::for_each_keyed;
+#[doc(hidden)]
+pub use list::{EachSignal, EachValue};
+pub use query::use_query_param;
+pub use reactive::{
+    Scope, Signal, Subscription, batch, create_effect, on_cleanup, untrack, use_memo, use_state,
+};
+#[doc(hidden)]
+pub use router::{__encode_path, __encode_segment};
+pub use router::{
+    Guard, LazyView, Location, Params, Resolution, Routable, Router, RouterMode, back, link,
+    match_path, navigate, navigate_replace, navigate_to, outlet, use_location, use_router,
+    use_routes, use_typed_router,
+};
+pub use storage::{
+    Backend, Persist, PersistError, StorageBackend, use_local_storage, use_persisted,
+};
+#[doc(hidden)]
+pub use switch::__tp_switch;
+pub use switch::switch_view;
 
 /// Call once at startup to get readable panic messages in the browser console.
 pub fn init() {
@@ -20,6 +57,15 @@ fn document() -> Document {
         .expect("no document")
 }
 
+/// A readable message for an exception thrown by a browser API, e.g.
+/// `"QuotaExceededError: The quota has been exceeded."`.
+fn error_message(err: &JsValue) -> String {
+    match err.dyn_ref::<js_sys::Error>() {
+        Some(err) => format!("{}: {}", err.name(), err.message()),
+        None => err.as_string().unwrap_or_else(|| format!("{err:?}")),
+    }
+}
+
 #[inline]
 pub fn create_element(tag: &str) -> Element {
     document()
@@ -27,6 +73,12 @@ pub fn create_element(tag: &str) -> Element {
         .unwrap_or_else(|_| panic!("failed to create <{}>", tag))
 }
 
+/// Creates an empty fragment; appending it to a node moves its children there.
+#[inline]
+pub fn create_fragment() -> DocumentFragment {
+    document().create_document_fragment()
+}
+
 #[inline]
 pub fn set_text_content(el: &Element, value: &dyn std::fmt::Display) {
     el.set_text_content(Some(&value.to_string()));
@@ -49,15 +101,25 @@ pub fn set_attribute(el: &Element, name: &str, value: &dyn std::fmt::Display) {
         .unwrap_or_else(|_| panic!("failed to set attribute {}", name));
 }
 
+/// Sets the `value` attribute and, for `<input>`s, the live value shown in the field.
 #[inline]
-pub fn append_child(parent: &Element, child: &Element) {
+pub fn set_value(el: &Element, value: &str) {
+    el.set_attribute("value", value)
+        .expect("failed to set value");
+    if let Some(input) = el.dyn_ref::<web_sys::HtmlInputElement>() {
+        input.set_value(value);
+    }
+}
+
+#[inline]
+pub fn append_child(parent: &Node, child: &Node) {
     parent
         .append_child(child.as_ref())
         .expect("failed to append child");
 }
 
 #[inline]
-pub fn append_text_node(parent: &Element, text: &str) {
+pub fn append_text_node(parent: &Node, text: &str) {
     let doc = document();
     let node: Text = doc.create_text_node(text);
     parent
@@ -66,106 +128,21 @@ pub fn append_text_node(parent: &Element, text: &str) {
 }
 
 pub fn set_onclick<F: FnMut() + 'static>(el: &Element, mut handler: F) {
-    let closure = Closure::<dyn FnMut(_)>::new(move |_event: web_sys::MouseEvent| {
-        handler();
-    });
-    el.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())
-        .expect("failed to add click listener");
-    closure.forget();
+    on(el, "click", move |_: web_sys::MouseEvent| handler());
 }
 
 pub fn set_oninput<F: FnMut(String) + 'static>(el: &Element, mut handler: F) {
-    let closure = Closure::<dyn FnMut(_)>::new(move |event: web_sys::InputEvent| {
+    on(el, "input", move |event: web_sys::InputEvent| {
         let target = event.target().expect("no target");
         let input: web_sys::HtmlInputElement = target.unchecked_into();
         handler(input.value());
     });
-    el.add_event_listener_with_callback("input", closure.as_ref().unchecked_ref())
-        .expect("failed to add input listener");
-    closure.forget();
 }
 
 pub fn set_onkeydown<F: FnMut(String) + 'static>(el: &Element, mut handler: F) {
-    let closure = Closure::<dyn FnMut(_)>::new(move |event: web_sys::KeyboardEvent| {
+    on(el, "keydown", move |event: web_sys::KeyboardEvent| {
         handler(event.key());
     });
-    el.add_event_listener_with_callback("keydown", closure.as_ref().unchecked_ref())
-        .expect("failed to add keydown listener");
-    closure.forget();
-}
-
-// ── Signal ────────────────────────────────────────────────────────────────────
-
-type Subscriber = Box<dyn Fn()>;
-
-struct SignalInner<T> {
-    value: T,
-    subscribers: Vec<Subscriber>,
-}
-
-/// Reactive value. Cloning shares the same underlying state.
-pub struct Signal<T: Clone + 'static> {
-    inner: Rc<RefCell<SignalInner<T>>>,
-}
-
-impl<T: Clone + 'static> Clone for Signal<T> {
-    fn clone(&self) -> Self {
-        Signal {
-            inner: Rc::clone(&self.inner),
-        }
-    }
-}
-
-impl<T: Clone + 'static> Signal<T> {
-    fn new(value: T) -> Self {
-        Signal {
-            inner: Rc::new(RefCell::new(SignalInner {
-                value,
-                subscribers: Vec::new(),
-            })),
-        }
-    }
-
-    /// Returns the current value (cloned).
-    pub fn get(&self) -> T {
-        self.inner.borrow().value.clone()
-    }
-
-    /// Updates the value and notifies all subscribers.
-    pub fn set(&self, value: T) {
-        self.inner.borrow_mut().value = value;
-
-        // Index-based loop + raw pointer so a subscriber calling .set() again
-        // (re-entrant) doesn't panic on the RefCell borrow.
-        // SAFETY: Box<dyn Fn()> address is stable in a Vec that only grows;
-        // the Rc clone keeps it alive for the duration of the call.
-        let len = self.inner.borrow().subscribers.len();
-        for i in 0..len {
-            let rc = Rc::clone(&self.inner);
-            let fn_ptr: *const dyn Fn() = {
-                let guard = rc.borrow();
-                &*guard.subscribers[i] as *const dyn Fn()
-            };
-            unsafe { (*fn_ptr)() };
-            drop(rc);
-        }
-    }
-
-    /// Registers a callback that runs on every value change.
-    pub fn subscribe<F: Fn() + 'static>(&self, f: F) {
-        self.inner.borrow_mut().subscribers.push(Box::new(f));
-    }
-}
-
-impl<T: Clone + std::fmt::Display + 'static> std::fmt::Display for Signal<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.get())
-    }
-}
-
-/// Creates a reactive state value.
-pub fn use_state<T: Clone + 'static>(initial: T) -> Signal<T> {
-    Signal::new(initial)
 }
 
 // ── Mount ─────────────────────────────────────────────────────────────────────
@@ -236,152 +213,6 @@ pub fn use_interval<F: FnMut() + 'static>(callback: F, ms: i32) -> IntervalHandl
 /// Spawns an async block on the WASM executor.
 pub use wasm_bindgen_futures::spawn_local;
 
-// ── Local storage ─────────────────────────────────────────────────────────────
-
-/// Reactive signal backed by `localStorage`. Persists as JSON on every `.set()`.
-pub fn use_local_storage<T>(key: &'static str, default: T) -> Signal<T>
-where
-    T: Serialize + DeserializeOwned + Clone + 'static,
-{
-    let initial = web_sys::window()
-        .and_then(|w| w.local_storage().ok().flatten())
-        .and_then(|s| s.get_item(key).ok().flatten())
-        .and_then(|json| serde_json::from_str(&json).ok())
-        .unwrap_or(default);
-
-    let signal = Signal::new(initial);
-
-    let signal_for_sub = signal.clone();
-    signal.subscribe(move || {
-        if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
-            if let Ok(json) = serde_json::to_string(&signal_for_sub.get()) {
-                storage.set_item(key, &json).ok();
-            }
-        }
-    });
-
-    signal
-}
-
-// ── Hash router ───────────────────────────────────────────────────────────────
-
-/// Hash-based router. Renders the matching route into a container element.
-///
-/// Routes are matched against `window.location.hash` (e.g. `"#/"`, `"#/about"`).
-/// Falls back to the first route when no match is found.
-pub fn use_router(routes: Vec<(&'static str, Box<dyn Fn() -> Element + 'static>)>) -> Element {
-    let container = create_element("div");
-    let routes: Rc<Vec<(&'static str, Box<dyn Fn() -> Element>)>> = Rc::new(routes);
-
-    let container_render = container.clone();
-    let routes_render = Rc::clone(&routes);
-
-    let render: Rc<dyn Fn()> = Rc::new(move || {
-        let hash = web_sys::window()
-            .and_then(|w| w.location().hash().ok())
-            .unwrap_or_default();
-        let hash = if hash.is_empty() {
-            String::from("#/")
-        } else {
-            hash
-        };
-
-        while let Some(child) = container_render.first_child() {
-            container_render.remove_child(&child).ok();
-        }
-
-        let mut matched = false;
-        for (path, handler) in routes_render.iter() {
-            if hash == *path {
-                let el = handler();
-                container_render.append_child(el.as_ref()).ok();
-                matched = true;
-                break;
-            }
-        }
-
-        if !matched {
-            if let Some((_, handler)) = routes_render.first() {
-                let el = handler();
-                container_render.append_child(el.as_ref()).ok();
-            }
-        }
-    });
-
-    render();
-
-    let render_for_event = Rc::clone(&render);
-    let closure = Closure::<dyn FnMut(_)>::new(move |_event: web_sys::HashChangeEvent| {
-        render_for_event();
-    });
-    web_sys::window()
-        .expect("no window")
-        .add_event_listener_with_callback("hashchange", closure.as_ref().unchecked_ref())
-        .expect("failed to add hashchange listener");
-    closure.forget();
-
-    container
-}
-
-// ── Memo ──────────────────────────────────────────────────────────────────────
-
-/// Implemented for `Signal<T>` and tuples of up to three signals.
-pub trait Deps {
-    fn on_change<F: Fn() + 'static>(&self, f: F);
-}
-
-impl<T: Clone + 'static> Deps for Signal<T> {
-    fn on_change<F: Fn() + 'static>(&self, f: F) {
-        self.subscribe(f);
-    }
-}
-
-impl<T1, T2> Deps for (Signal<T1>, Signal<T2>)
-where
-    T1: Clone + 'static,
-    T2: Clone + 'static,
-{
-    fn on_change<F: Fn() + 'static>(&self, f: F) {
-        let f = Rc::new(f);
-        let f1 = Rc::clone(&f);
-        self.0.subscribe(move || f1());
-        self.1.subscribe(move || f());
-    }
-}
-
-impl<T1, T2, T3> Deps for (Signal<T1>, Signal<T2>, Signal<T3>)
-where
-    T1: Clone + 'static,
-    T2: Clone + 'static,
-    T3: Clone + 'static,
-{
-    fn on_change<F: Fn() + 'static>(&self, f: F) {
-        let f = Rc::new(f);
-        let f1 = Rc::clone(&f);
-        let f2 = Rc::clone(&f);
-        self.0.subscribe(move || f1());
-        self.1.subscribe(move || f2());
-        self.2.subscribe(move || f());
-    }
-}
-
-/// Computed signal that re-evaluates whenever a dependency changes.
-pub fn use_memo<T, D, F>(deps: D, compute: F) -> Signal<T>
-where
-    T: Clone + 'static,
-    D: Deps,
-    F: Fn() -> T + 'static,
-{
-    let compute = Rc::new(compute);
-    let result = Signal::new(compute());
-    let result_clone = result.clone();
-    let compute_clone = Rc::clone(&compute);
-    deps.on_change(move || {
-        result_clone.set(compute_clone());
-    });
-    result
-}
-
 // ── Components ────────────────────────────────────────────────────────────────
 
 /// Struct-based component. For most cases, plain functions returning `Element` are simpler.
@@ -392,9 +223,13 @@ pub trait Component {
 // ── Prelude ───────────────────────────────────────────────────────────────────
 
 pub mod prelude {
+    pub use super::web_sys::{self, Element};
     pub use super::{
-        Component, Deps, IntervalHandle, Signal, init, mount, mount_to, spawn_local, tp,
-        use_effect, use_interval, use_local_storage, use_memo, use_router, use_state,
+        Backend, Component, Guard, IndexedDb, IntervalHandle, Location, Params, Persist, Routable,
+        Router, RouterMode, Signal, back, batch, create_effect, for_each_keyed, init, link, listen,
+        mount, mount_to, navigate, navigate_replace, navigate_to, outlet, spawn_local, switch_view,
+        tp, untrack, use_effect, use_indexed_db, use_interval, use_local_storage, use_location,
+        use_memo, use_persisted, use_query_param, use_router, use_routes, use_state,
+        use_typed_router,
     };
-    pub use web_sys::Element;
 }
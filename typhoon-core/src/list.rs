@@ -0,0 +1,474 @@
+//! Keyed list rendering.
+//!
+//! `tp!`'s `for pat in expr { ... }` child goes through [`EachSignal`] /
+//! [`EachValue`], picked by the same method-resolution trick as the value
+//! bindings in `bind.rs`: a `Signal<Vec<T>>` or a closure renders a live list,
+//! any other `IntoIterator + Clone` is rendered once.
+
+use std::{cell::RefCell, collections::HashMap, hash::Hash, rc::Rc};
+
+use web_sys::Node;
+
+use crate::{Scope, Signal, create_effect, create_fragment, document, untrack};
+
+/// Renders one element per item of `items` into `parent`, reconciling by key.
+///
+/// Whenever `items` changes, rows are matched up with the previous render by
+/// `key`: existing elements are kept (and moved only if their position
+/// changed), new keys are rendered with `render`, and elements whose key
+/// disappeared are removed. Kept rows are **not** re-rendered, so focus,
+/// input state and running CSS transitions survive the update.
+///
+/// Rows are inserted before an empty marker node appended to `parent`, so the
+/// list can share its parent with other children. Each row is rendered in its
+/// own [`Scope`], disposed when the row is removed. `render` may return a
+/// `DocumentFragment` to give a row several nodes.
+///
+/// ```ignore
+/// let list = tp! { ul };
+/// for_each_keyed(&list, &todos, |t| t.id, |t| tp! { li.text(&t.text) });
+/// ```
+pub fn for_each_keyed<T, K, KF, RF, N>(parent: &Node, items: &Signal<Vec<T>>, key: KF, render: RF)
+where
+    T: Clone + 'static,
+    K: Eq + Hash + Clone + 'static,
+    KF: Fn(&T) -> K + 'static,
+    RF: Fn(&T) -> N + 'static,
+    N: Into<Node>,
+{
+    let items = items.clone();
+    ReactiveList(move || items.get()).keyed(parent, key, render);
+}
+
+// ── tp! `for` support ─────────────────────────────────────────────────────────
+
+/// A list re-read (and tracked) on every change.
+#[doc(hidden)]
+pub struct ReactiveList<F>(F);
+
+/// A list rendered once.
+#[doc(hidden)]
+pub struct StaticList<I>(I);
+
+/// Picks [`ReactiveList`] for a `Signal<Vec<T>>` or a closure returning an iterator.
+#[doc(hidden)]
+pub trait EachSignal {
+    type List;
+    fn __list(&self) -> Self::List;
+}
+
+impl<T: Clone + 'static> EachSignal for Signal<Vec<T>> {
+    type List = ReactiveList<Box<dyn Fn() -> Vec<T>>>;
+    fn __list(&self) -> Self::List {
+        let signal = self.clone();
+        ReactiveList(Box::new(move || signal.get()))
+    }
+}
+
+impl<F, I> EachSignal for F
+where
+    F: Fn() -> I + Clone + 'static,
+    I: IntoIterator,
+{
+    type List = ReactiveList<F>;
+    fn __list(&self) -> Self::List {
+        ReactiveList(self.clone())
+    }
+}
+
+/// Picks [`StaticList`] for any other iterable.
+#[doc(hidden)]
+pub trait EachValue {
+    type List;
+    fn __list(&self) -> Self::List;
+}
+
+impl<I: IntoIterator + Clone> EachValue for &I {
+    type List = StaticList<I>;
+    fn __list(&self) -> Self::List {
+        StaticList((*self).clone())
+    }
+}
+
+impl<F, I> ReactiveList<F>
+where
+    F: Fn() -> I + 'static,
+    I: IntoIterator,
+    I::Item: 'static,
+{
+    /// Reconciles rows by `key`; see [`for_each_keyed`].
+    pub fn keyed<K, KF, RF, N>(self, parent: &Node, key: KF, render: RF)
+    where
+        K: Eq + Hash + Clone + 'static,
+        KF: Fn(&I::Item) -> K + 'static,
+        RF: Fn(&I::Item) -> N + 'static,
+        N: Into<Node>,
+    {
+        let items = self.0;
+        let anchor = append_anchor(parent);
+        let rows: Rc<RefCell<Vec<Row<K>>>> = Rc::new(RefCell::new(Vec::new()));
+        // Rows outlive individual effect runs, so they hang off this scope
+        // rather than the effect's own.
+        let list_scope = Scope::new();
+
+        create_effect(move || {
+            let items: Vec<I::Item> = items().into_iter().collect();
+            untrack(|| {
+                let old = std::mem::take(&mut *rows.borrow_mut());
+                let new = reconcile(&anchor, &list_scope, old, &items, &key, &render);
+                *rows.borrow_mut() = new;
+            });
+        });
+    }
+
+    /// Re-renders every row whenever the list changes.
+    pub fn each<RF, N>(self, parent: &Node, render: RF)
+    where
+        RF: Fn(&I::Item) -> N + 'static,
+        N: Into<Node>,
+    {
+        let items = self.0;
+        let anchor = append_anchor(parent);
+        let rows: RefCell<Vec<Row<()>>> = RefCell::new(Vec::new());
+        let list_scope = Scope::new();
+
+        create_effect(move || {
+            let items: Vec<I::Item> = items().into_iter().collect();
+            untrack(|| {
+                let Some(parent) = anchor.parent_node() else {
+                    return;
+                };
+                for row in rows.take() {
+                    row.remove(&parent);
+                }
+                let new: Vec<Row<()>> = items
+                    .iter()
+                    .map(|item| {
+                        let row = Row::render((), &list_scope, || render(item));
+                        row.insert_before(&parent, &anchor);
+                        row
+                    })
+                    .collect();
+                *rows.borrow_mut() = new;
+            });
+        });
+    }
+}
+
+impl<I: IntoIterator> StaticList<I> {
+    /// Keys only matter for updates, so a static list ignores them.
+    pub fn keyed<K, KF, RF, N>(self, parent: &Node, _key: KF, render: RF)
+    where
+        KF: Fn(&I::Item) -> K,
+        RF: Fn(&I::Item) -> N,
+        N: Into<Node>,
+    {
+        self.each(parent, render);
+    }
+
+    pub fn each<RF, N>(self, parent: &Node, render: RF)
+    where
+        RF: Fn(&I::Item) -> N,
+        N: Into<Node>,
+    {
+        for item in self.0 {
+            parent
+                .append_child(&render(&item).into())
+                .expect("failed to append list row");
+        }
+    }
+}
+
+fn append_anchor(parent: &Node) -> Node {
+    let anchor: Node = document().create_text_node("").into();
+    parent
+        .append_child(&anchor)
+        .expect("failed to append list anchor");
+    anchor
+}
+
+struct Row<K> {
+    key: K,
+    /// Empty text nodes around the row's content. Everything between them
+    /// belongs to the row, including nodes a reactive `if` or `for` at the
+    /// row's root inserts after the first render.
+    start: Node,
+    end: Node,
+    scope: Scope,
+}
+
+impl<K> Row<K> {
+    /// Renders a row into a detached fragment, ready to be inserted.
+    fn render<N: Into<Node>>(key: K, list_scope: &Scope, render: impl FnOnce() -> N) -> Self {
+        let scope = list_scope.run(Scope::new);
+        let node: Node = scope.run(render).into();
+        let start: Node = document().create_text_node("").into();
+        let end: Node = document().create_text_node("").into();
+        let fragment = create_fragment();
+        for node in [&start, &node, &end] {
+            fragment
+                .append_child(node)
+                .expect("failed to build list row");
+        }
+        Row {
+            key,
+            start,
+            end,
+            scope,
+        }
+    }
+
+    /// The row's nodes in document order, markers included.
+    ///
+    /// Only nodes still in `parent` count. If something else took the start
+    /// marker away there is nothing left to move; if it took the end marker,
+    /// the row stops at its start marker instead of running into the rows
+    /// after it.
+    fn nodes(&self, parent: &Node) -> Vec<Node> {
+        if !is_child(&self.start, parent) {
+            return Vec::new();
+        }
+        if !is_child(&self.end, parent) {
+            return vec![self.start.clone()];
+        }
+        let mut nodes = vec![self.start.clone()];
+        let mut node = self.start.clone();
+        while !node.is_same_node(Some(&self.end)) {
+            let Some(next) = node.next_sibling() else {
+                break;
+            };
+            nodes.push(next.clone());
+            node = next;
+        }
+        nodes
+    }
+
+    fn insert_before(&self, parent: &Node, next: &Node) {
+        for node in self.nodes(parent) {
+            parent
+                .insert_before(&node, Some(next))
+                .expect("failed to insert list row");
+        }
+    }
+
+    fn remove(self, parent: &Node) {
+        for node in self.nodes(parent) {
+            parent.remove_child(&node).ok();
+        }
+        self.scope.dispose();
+    }
+}
+
+fn is_child(node: &Node, parent: &Node) -> bool {
+    node.parent_node()
+        .is_some_and(|p| p.is_same_node(Some(parent)))
+}
+
+/// Brings the rows before `anchor` from `old` to the order given by `items`,
+/// touching as few DOM nodes as possible.
+fn reconcile<T, K, KF, RF, N>(
+    anchor: &Node,
+    list_scope: &Scope,
+    old: Vec<Row<K>>,
+    items: &[T],
+    key: &KF,
+    render: &RF,
+) -> Vec<Row<K>>
+where
+    K: Eq + Hash + Clone,
+    KF: Fn(&T) -> K,
+    RF: Fn(&T) -> N,
+    N: Into<Node>,
+{
+    let Some(parent) = anchor.parent_node() else {
+        return old;
+    };
+
+    let old_keys: Vec<K> = old.iter().map(|row| row.key.clone()).collect();
+    let new_keys: Vec<K> = items.iter().map(key).collect();
+    let Plan {
+        sources,
+        removed,
+        stable,
+    } = plan(&old_keys, &new_keys);
+
+    let mut old: Vec<Option<Row<K>>> = old.into_iter().map(Some).collect();
+    for i in removed {
+        if let Some(row) = old[i].take() {
+            row.remove(&parent);
+        }
+    }
+
+    let rows: Vec<Row<K>> = sources
+        .iter()
+        .zip(items)
+        .zip(new_keys)
+        .map(|((src, item), key)| match src.and_then(|i| old[i].take()) {
+            Some(row) => row,
+            None => Row::render(key, list_scope, || render(item)),
+        })
+        .collect();
+
+    let mut next: Node = anchor.clone();
+    for (row, stable) in rows.iter().zip(stable).rev() {
+        if !stable {
+            row.insert_before(&parent, &next);
+        }
+        next = row.start.clone();
+    }
+
+    rows
+}
+
+/// How to get from the old rows to the new ones, worked out from keys alone.
+#[derive(Debug, PartialEq)]
+struct Plan {
+    /// For every new position, the old row it reuses (if any).
+    sources: Vec<Option<usize>>,
+    /// Old rows that aren't reused, in order.
+    removed: Vec<usize>,
+    /// For every new position, whether its row is reused and can stay put.
+    stable: Vec<bool>,
+}
+
+fn plan<K: Eq + Hash>(old: &[K], new: &[K]) -> Plan {
+    let mut old_index: HashMap<&K, usize> = HashMap::with_capacity(old.len());
+    for (i, key) in old.iter().enumerate() {
+        old_index.entry(key).or_insert(i);
+    }
+
+    // Removing from the map means a duplicate key gets a fresh row instead
+    // of stealing the same node twice.
+    let sources: Vec<Option<usize>> = new.iter().map(|k| old_index.remove(k)).collect();
+
+    let mut used = vec![false; old.len()];
+    for &i in sources.iter().flatten() {
+        used[i] = true;
+    }
+    let removed = (0..old.len()).filter(|&i| !used[i]).collect();
+
+    // Rows whose old positions form the longest increasing run can stay put;
+    // everything else is moved or freshly inserted.
+    let reused: Vec<usize> = sources.iter().flatten().copied().collect();
+    let mut stable = vec![false; sources.len()];
+    let mut lis = longest_increasing_subsequence(&reused)
+        .into_iter()
+        .peekable();
+    let mut nth_reused = 0;
+    for (pos, src) in sources.iter().enumerate() {
+        if src.is_some() {
+            if lis.peek() == Some(&nth_reused) {
+                stable[pos] = true;
+                lis.next();
+            }
+            nth_reused += 1;
+        }
+    }
+
+    Plan {
+        sources,
+        removed,
+        stable,
+    }
+}
+
+/// Returns the positions in `seq` of one longest strictly increasing subsequence.
+fn longest_increasing_subsequence(seq: &[usize]) -> Vec<usize> {
+    // tails[k] = position of the smallest tail of an increasing run of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev: Vec<Option<usize>> = vec![None; seq.len()];
+
+    for (i, &value) in seq.iter().enumerate() {
+        let k = tails.partition_point(|&t| seq[t] < value);
+        if k > 0 {
+            prev[i] = Some(tails[k - 1]);
+        }
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut out = Vec::with_capacity(tails.len());
+    let mut cur = tails.last().copied();
+    while let Some(i) = cur {
+        out.push(i);
+        cur = prev[i];
+    }
+    out.reverse();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan_of(old: &str, new: &str) -> Plan {
+        let old: Vec<char> = old.chars().collect();
+        let new: Vec<char> = new.chars().collect();
+        plan(&old, &new)
+    }
+
+    #[test]
+    fn lis_finds_a_longest_increasing_run() {
+        assert!(longest_increasing_subsequence(&[]).is_empty());
+        assert_eq!(longest_increasing_subsequence(&[0, 1, 2]), [0, 1, 2]);
+        assert_eq!(longest_increasing_subsequence(&[2, 1, 0]).len(), 1);
+
+        let seq = [3, 1, 2, 5, 4, 6];
+        let positions = longest_increasing_subsequence(&seq);
+        assert_eq!(positions.len(), 4);
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        assert!(positions.windows(2).all(|w| seq[w[0]] < seq[w[1]]));
+    }
+
+    #[test]
+    fn unchanged_list_stays_put() {
+        let plan = plan_of("abc", "abc");
+        assert_eq!(plan.sources, [Some(0), Some(1), Some(2)]);
+        assert!(plan.removed.is_empty());
+        assert_eq!(plan.stable, [true, true, true]);
+    }
+
+    #[test]
+    fn reorder_moves_only_rows_out_of_place() {
+        // Moving the last row to the front moves just that row.
+        let plan = plan_of("abcd", "dabc");
+        assert_eq!(plan.sources, [Some(3), Some(0), Some(1), Some(2)]);
+        assert!(plan.removed.is_empty());
+        assert_eq!(plan.stable, [false, true, true, true]);
+
+        let plan = plan_of("abc", "cba");
+        assert_eq!(plan.stable.iter().filter(|s| **s).count(), 1);
+    }
+
+    #[test]
+    fn insert_renders_only_new_rows() {
+        let plan = plan_of("ac", "abcd");
+        assert_eq!(plan.sources, [Some(0), None, Some(1), None]);
+        assert!(plan.removed.is_empty());
+        assert_eq!(plan.stable, [true, false, true, false]);
+    }
+
+    #[test]
+    fn remove_drops_missing_keys() {
+        let plan = plan_of("abcd", "bd");
+        assert_eq!(plan.sources, [Some(1), Some(3)]);
+        assert_eq!(plan.removed, [0, 2]);
+        assert_eq!(plan.stable, [true, true]);
+    }
+
+    #[test]
+    fn duplicate_keys_get_their_own_rows() {
+        // The second `a` can't reuse the first one's row.
+        let plan = plan_of("ab", "aab");
+        assert_eq!(plan.sources, [Some(0), None, Some(1)]);
+        assert_eq!(plan.stable, [true, false, true]);
+
+        // Old duplicates beyond the first are dropped.
+        let plan = plan_of("aa", "a");
+        assert_eq!(plan.sources, [Some(0)]);
+        assert_eq!(plan.removed, [1]);
+    }
+}
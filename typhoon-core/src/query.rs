@@ -0,0 +1,243 @@
+//! Signals synced with the URL's query string.
+//!
+//! Values are stored as JSON, like [`use_local_storage`](crate::use_local_storage),
+//! except that strings are written bare when that is unambiguous, so a search
+//! box gives `?q=rust` rather than `?q=%22rust%22`. Hand-written URLs with a
+//! bare string where JSON was expected still parse.
+
+use std::rc::Rc;
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::router::{Location, decode, navigate_replace};
+use crate::{Signal, create_effect, untrack, use_location};
+
+/// Reactive signal backed by one query-string parameter.
+///
+/// The signal starts from the URL (or `default` when the parameter is
+/// missing or doesn't parse) and follows it on Back/Forward and navigation.
+/// Setting it rewrites the parameter with `replaceState`, so typing in a
+/// filter box doesn't add history entries; setting it back to `default`
+/// removes the parameter.
+///
+/// ```ignore
+/// let page = use_query_param("page", 1u32);
+/// let search = use_query_param("q", String::new());
+/// button.onclick(move || page.set(page.get() + 1))   // → ?page=2
+/// ```
+pub fn use_query_param<T>(name: &str, default: T) -> Signal<T>
+where
+    T: Serialize + DeserializeOwned + Clone + PartialEq + 'static,
+{
+    let location = use_location();
+    let name: Rc<str> = name.into();
+    let read = {
+        let name = Rc::clone(&name);
+        let default = default.clone();
+        move |query: &str| {
+            query_param(query, &name)
+                .and_then(|raw| decode_value(&raw))
+                .unwrap_or_else(|| default.clone())
+        }
+    };
+    let signal = Signal::new(read(&untrack(|| location.get()).query));
+
+    // URL → signal. Skipping equal values keeps the write below from
+    // echoing straight back.
+    let from_url = signal.clone();
+    let url = location.clone();
+    create_effect(move || {
+        let value = read(&url.get().query);
+        if untrack(|| from_url.get()) != value {
+            from_url.set(value);
+        }
+    });
+
+    // Signal → URL.
+    let to_url = signal.clone();
+    signal
+        .subscribe(move || {
+            let value = to_url.get();
+            let raw = if value == default {
+                None
+            } else {
+                match encode_value(&value) {
+                    Some(raw) => Some(raw),
+                    None => return,
+                }
+            };
+            let current = untrack(|| location.get());
+            let query = with_query_param(&current.query, &name, raw.as_deref());
+            if query != current.query {
+                let url = Location { query, ..current }.to_url();
+                untrack(|| navigate_replace(&url));
+            }
+        })
+        .forget();
+
+    signal
+}
+
+/// The decoded value of the first `name` parameter in `query`.
+fn query_param(query: &str, name: &str) -> Option<String> {
+    pairs(query)
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+}
+
+/// `query` with `name` set to `value` (or removed when `None`). The
+/// parameter keeps its position; other parameters are left untouched.
+fn with_query_param(query: &str, name: &str, value: Option<&str>) -> String {
+    let mut value = value.map(|v| format!("{}={}", encode(name), encode(v)));
+    let mut parts = Vec::new();
+    for part in query.split('&').filter(|p| !p.is_empty()) {
+        let key = part.split_once('=').map_or(part, |(key, _)| key);
+        if decode_component(key) != name {
+            parts.push(part.to_owned());
+        } else if let Some(value) = value.take() {
+            parts.push(value);
+        }
+    }
+    parts.extend(value);
+    parts.join("&")
+}
+
+fn pairs(query: &str) -> impl Iterator<Item = (String, String)> {
+    query.split('&').filter(|p| !p.is_empty()).map(|part| {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        (decode_component(key), decode_component(value))
+    })
+}
+
+/// Decodes a query key or value: `+` is a space.
+fn decode_component(raw: &str) -> String {
+    decode(&raw.replace('+', " "))
+}
+
+/// Percent-encodes a query key or value.
+fn encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => out.push(byte as char),
+            b'-' | b'.' | b'_' | b'~' | b'!' | b'$' | b'\'' | b'(' | b')' | b'*' | b',' | b';'
+            | b':' | b'@' | b'/' | b'?' => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+/// Parses a parameter as JSON, falling back to the raw text as a string.
+fn decode_value<T: DeserializeOwned>(raw: &str) -> Option<T> {
+    serde_json::from_str(raw)
+        .ok()
+        .or_else(|| serde_json::from_value(Value::String(raw.to_owned())).ok())
+}
+
+/// Formats a value as JSON, writing strings bare unless they would read back
+/// as JSON.
+fn encode_value<T: Serialize>(value: &T) -> Option<String> {
+    match serde_json::to_value(value).ok()? {
+        Value::String(s) if serde_json::from_str::<Value>(&s).is_err() => Some(s),
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    #[test]
+    fn params_are_read_and_decoded() {
+        let query = "q=hello+world&tag=a%26b&empty=&flag&q=second";
+        assert_eq!(query_param(query, "q").as_deref(), Some("hello world"));
+        assert_eq!(query_param(query, "tag").as_deref(), Some("a&b"));
+        assert_eq!(query_param(query, "empty").as_deref(), Some(""));
+        assert_eq!(query_param(query, "flag").as_deref(), Some(""));
+        assert_eq!(query_param(query, "missing"), None);
+        assert_eq!(query_param("", "q"), None);
+    }
+
+    #[test]
+    fn params_are_set_in_place() {
+        assert_eq!(with_query_param("", "page", Some("2")), "page=2");
+        assert_eq!(
+            with_query_param("a=1&page=1&b=2", "page", Some("3")),
+            "a=1&page=3&b=2"
+        );
+        assert_eq!(
+            with_query_param("a=1", "q", Some("a b&c")),
+            "a=1&q=a%20b%26c"
+        );
+        assert_eq!(
+            with_query_param("a=1&page=1&page=2", "page", Some("3")),
+            "a=1&page=3"
+        );
+        assert_eq!(with_query_param("a=1&page=1", "page", None), "a=1");
+        assert_eq!(with_query_param("page=1", "page", None), "");
+        assert_eq!(with_query_param("a+b=1", "a b", None), "");
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Filter {
+        min: u32,
+        tags: Vec<String>,
+    }
+
+    fn round_trip<T>(value: T) -> T
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let raw = encode_value(&value).unwrap();
+        let query = with_query_param("", "v", Some(&raw));
+        decode_value(&query_param(&query, "v").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn values_round_trip() {
+        assert_eq!(round_trip(42u32), 42);
+        assert_eq!(round_trip(-1.5f64), -1.5);
+        assert!(round_trip(true));
+        assert_eq!(round_trip(Some(3u8)), Some(3));
+        assert_eq!(round_trip(None::<u8>), None);
+        assert_eq!(round_trip(vec![1, 2, 3]), vec![1, 2, 3]);
+        let filter = Filter {
+            min: 3,
+            tags: vec!["a&b".into(), "c d".into()],
+        };
+        assert_eq!(
+            round_trip(filter),
+            Filter {
+                min: 3,
+                tags: vec!["a&b".into(), "c d".into()],
+            }
+        );
+        for s in [
+            "rust",
+            "hello world",
+            "42",
+            "true",
+            "null",
+            "\"quoted\"",
+            "a=b&c",
+            "",
+        ] {
+            assert_eq!(round_trip(s.to_owned()), s);
+        }
+        assert_eq!(round_trip(Some("null".to_owned())), Some("null".to_owned()));
+    }
+
+    #[test]
+    fn strings_are_written_bare_when_unambiguous() {
+        assert_eq!(encode_value(&"rust").as_deref(), Some("rust"));
+        assert_eq!(encode_value(&"42").as_deref(), Some("\"42\""));
+        assert_eq!(encode_value(&7).as_deref(), Some("7"));
+        assert_eq!(decode_value::<String>("rust").as_deref(), Some("rust"));
+        assert_eq!(decode_value::<String>("\"rust\"").as_deref(), Some("rust"));
+        assert_eq!(decode_value::<u32>("rust"), None);
+    }
+}
@@ -0,0 +1,1064 @@
+//! Signals, derived values and effects with automatic dependency tracking.
+//!
+//! While a computation (a memo or an effect) runs, every [`Signal::get`] it
+//! performs registers that signal as a dependency. When any of those signals
+//! changes, the computation re-runs and its dependency set is rebuilt from
+//! scratch, so branches that stop reading a signal also stop reacting to it.
+//!
+//! Ownership is tracked through [`Scope`]s: effects, memos, forgotten
+//! [`Subscription`]s and [`on_cleanup`] callbacks created while a scope is
+//! current are torn down when that scope is disposed. Every effect run also
+//! gets a fresh scope, so whatever the previous run created is disposed first.
+//!
+//! Updates are glitch-free: [`Signal::set`] only queues the work it causes.
+//! The queue then runs memos lowest-height first (a memo's height is one more
+//! than the highest of its sources), so every memo sees settled inputs and
+//! runs at most once, and only then runs effects and subscribers. [`batch`]
+//! holds the queue until several signals have been written.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::{Rc, Weak},
+};
+
+thread_local! {
+    /// The computation currently collecting dependencies, if any.
+    static OBSERVER: RefCell<Option<Rc<Computation>>> = const { RefCell::new(None) };
+    /// The scope that owns whatever is being created right now, if any.
+    static OWNER: RefCell<Option<Rc<ScopeInner>>> = const { RefCell::new(None) };
+    /// Work caused by signal writes that hasn't run yet.
+    static QUEUE: RefCell<Queue> = const {
+        RefCell::new(Queue { memos: Vec::new(), effects: VecDeque::new() })
+    };
+    static BATCH_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static FLUSHING: Cell<bool> = const { Cell::new(false) };
+}
+
+// ── Scope ─────────────────────────────────────────────────────────────────────
+
+struct ScopeInner {
+    parent: Weak<ScopeInner>,
+    children: RefCell<Vec<Rc<ScopeInner>>>,
+    cleanups: RefCell<Vec<Box<dyn FnOnce()>>>,
+    disposed: Cell<bool>,
+}
+
+impl ScopeInner {
+    /// Disposes child scopes, then runs cleanups newest-first.
+    fn clear(&self) {
+        for child in self.children.take() {
+            child.dispose();
+        }
+        let cleanups = self.cleanups.take();
+        for cleanup in cleanups.into_iter().rev() {
+            cleanup();
+        }
+    }
+
+    fn dispose(self: &Rc<Self>) {
+        if self.disposed.replace(true) {
+            return;
+        }
+        self.clear();
+        if let Some(parent) = self.parent.upgrade() {
+            parent
+                .children
+                .borrow_mut()
+                .retain(|c| !Rc::ptr_eq(c, self));
+        }
+    }
+}
+
+/// Owner of the effects, memos and subscriptions created inside it.
+///
+/// Render a component inside [`Scope::run`] and call [`Scope::dispose`] when
+/// the component is removed from the page; everything it set up is torn down
+/// with it. Scopes nest: a scope created while another one is current is
+/// disposed together with its parent.
+///
+/// ```ignore
+/// let scope = Scope::new();
+/// let view = scope.run(|| profile_page());
+/// // ... later, when the view goes away:
+/// scope.dispose();
+/// ```
+#[derive(Clone)]
+pub struct Scope {
+    inner: Rc<ScopeInner>,
+}
+
+impl Scope {
+    /// Creates a scope owned by the current one (if any). Under a disposed
+    /// scope, the new one starts out disposed too.
+    pub fn new() -> Self {
+        let parent = OWNER.with(|o| o.borrow().clone());
+        let disposed = parent.as_ref().is_some_and(|p| p.disposed.get());
+        let inner = Rc::new(ScopeInner {
+            parent: parent.as_ref().map(Rc::downgrade).unwrap_or_default(),
+            children: RefCell::new(Vec::new()),
+            cleanups: RefCell::new(Vec::new()),
+            disposed: Cell::new(disposed),
+        });
+        if let Some(parent) = parent
+            && !disposed
+        {
+            parent.children.borrow_mut().push(Rc::clone(&inner));
+        }
+        Scope { inner }
+    }
+
+    /// Runs `f` with this scope as the owner of anything it creates.
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        let prev = OWNER.with(|o| o.replace(Some(Rc::clone(&self.inner))));
+        let _restore = Restore::new(|| OWNER.with(|o| *o.borrow_mut() = prev));
+        f()
+    }
+
+    /// Tears down everything owned by this scope and its child scopes.
+    /// Calling it again is a no-op.
+    pub fn dispose(&self) {
+        self.inner.dispose();
+    }
+
+    pub(crate) fn is_disposed(&self) -> bool {
+        self.inner.disposed.get()
+    }
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Scope::new()
+    }
+}
+
+/// Registers `f` to run when the current [`Scope`] is disposed (or, inside an
+/// effect, before the effect's next run). Outside any scope `f` never runs;
+/// in a scope that is already disposed it runs straight away.
+pub fn on_cleanup(f: impl FnOnce() + 'static) {
+    let Some(owner) = OWNER.with(|o| o.borrow().clone()) else {
+        return;
+    };
+    if owner.disposed.get() {
+        f();
+    } else {
+        owner.cleanups.borrow_mut().push(Box::new(f));
+    }
+}
+
+/// Whether [`on_cleanup`] would register its callback right now.
+pub(crate) fn has_owner() -> bool {
+    OWNER.with(|o| o.borrow().is_some())
+}
+
+// ── Computations ──────────────────────────────────────────────────────────────
+
+/// A memo or effect body plus the signals it read on its last run.
+struct Computation {
+    f: Box<dyn Fn()>,
+    sources: RefCell<Vec<Weak<dyn Source>>>,
+    /// Owns what the body created on its last run.
+    owner: Scope,
+    /// Memos feed other computations and are scheduled by height; effects
+    /// only run once all memos have settled.
+    memo: bool,
+    height: Cell<usize>,
+    queued: Cell<bool>,
+    disposed: Cell<bool>,
+}
+
+impl Computation {
+    /// Creates a computation owned by the current scope.
+    fn new(memo: bool, f: impl Fn() + 'static) -> Rc<Self> {
+        let computation = Rc::new(Computation {
+            f: Box::new(f),
+            sources: RefCell::new(Vec::new()),
+            owner: Scope::new(),
+            memo,
+            height: Cell::new(0),
+            queued: Cell::new(false),
+            disposed: Cell::new(false),
+        });
+        let weak = Rc::downgrade(&computation);
+        on_cleanup(move || {
+            if let Some(computation) = weak.upgrade() {
+                computation.dispose();
+            }
+        });
+        computation
+    }
+
+    /// Re-runs the body, replacing the old dependency set with the new one.
+    fn run(self: &Rc<Self>) {
+        if self.disposed.get() {
+            return;
+        }
+        self.unlink_sources();
+        self.owner.inner.clear();
+        self.height.set(0);
+        let prev = OBSERVER.with(|o| o.replace(Some(Rc::clone(self))));
+        let _restore = Restore::new(|| OBSERVER.with(|o| *o.borrow_mut() = prev));
+        self.owner.run(|| (self.f)());
+    }
+
+    fn unlink_sources(self: &Rc<Self>) {
+        for source in self.sources.take() {
+            if let Some(source) = source.upgrade() {
+                source.unlink(self);
+            }
+        }
+    }
+
+    fn dispose(self: &Rc<Self>) {
+        if self.disposed.replace(true) {
+            return;
+        }
+        self.unlink_sources();
+        self.owner.dispose();
+    }
+}
+
+/// Type-erased view of a signal, so a computation can unlink itself from
+/// sources of any value type.
+trait Source {
+    fn unlink(&self, observer: &Rc<Computation>);
+}
+
+// ── Scheduling ────────────────────────────────────────────────────────────────
+
+/// Runs its closure when dropped, so runtime state is put back even if the
+/// user code in between panics.
+struct Restore<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Restore<F> {
+    fn new(f: F) -> Self {
+        Restore(Some(f))
+    }
+}
+
+impl<F: FnOnce()> Drop for Restore<F> {
+    fn drop(&mut self) {
+        if let Some(f) = self.0.take() {
+            f();
+        }
+    }
+}
+
+enum Task {
+    Computation(Rc<Computation>),
+    Subscriber(Subscriber),
+}
+
+struct Queue {
+    memos: Vec<Rc<Computation>>,
+    effects: VecDeque<Task>,
+}
+
+impl Queue {
+    fn push_computation(&mut self, computation: &Rc<Computation>) {
+        if computation.queued.replace(true) {
+            return;
+        }
+        if computation.memo {
+            self.memos.push(Rc::clone(computation));
+        } else {
+            self.effects
+                .push_back(Task::Computation(Rc::clone(computation)));
+        }
+    }
+
+    fn push_subscriber(&mut self, f: &Subscriber) {
+        if f.queued.replace(true) {
+            return;
+        }
+        self.effects.push_back(Task::Subscriber(Rc::clone(f)));
+    }
+
+    /// The lowest pending memo, or else the oldest effect/subscriber.
+    fn pop(&mut self) -> Option<Task> {
+        let lowest = (0..self.memos.len()).min_by_key(|&i| self.memos[i].height.get());
+        let task = match lowest {
+            Some(i) => Some(Task::Computation(self.memos.swap_remove(i))),
+            None => self.effects.pop_front(),
+        };
+        match &task {
+            Some(Task::Computation(c)) => c.queued.set(false),
+            Some(Task::Subscriber(f)) => f.queued.set(false),
+            None => {}
+        }
+        task
+    }
+}
+
+/// Runs queued work until nothing is left. Writes made while flushing are
+/// queued and picked up by the same loop.
+fn flush() {
+    if FLUSHING.replace(true) {
+        return;
+    }
+    let _restore = Restore::new(|| FLUSHING.set(false));
+    while let Some(task) = QUEUE.with(|q| q.borrow_mut().pop()) {
+        match task {
+            Task::Computation(c) => c.run(),
+            Task::Subscriber(f) => {
+                // Unsubscribed after being queued.
+                if f.active.get() {
+                    untrack(|| (f.f)());
+                }
+            }
+        }
+    }
+}
+
+/// Applies several signal writes as one update.
+///
+/// Memos, effects and subscribers affected by the writes inside `f` run once
+/// `f` returns, at most once each, and never see some writes applied but not
+/// others. Memos read inside `f` still hold their pre-batch value.
+///
+/// ```ignore
+/// batch(|| {
+///     first.set("Ada".into());
+///     last.set("Lovelace".into());
+/// }); // `full_name` recomputes once
+/// ```
+pub fn batch<R>(f: impl FnOnce() -> R) -> R {
+    BATCH_DEPTH.with(|d| d.set(d.get() + 1));
+    let out = {
+        let _restore = Restore::new(|| BATCH_DEPTH.with(|d| d.set(d.get() - 1)));
+        f()
+    };
+    if BATCH_DEPTH.with(Cell::get) == 0 {
+        flush();
+    }
+    out
+}
+
+/// Runs `f` without registering any signal it reads as a dependency of the
+/// surrounding memo or effect.
+pub fn untrack<R>(f: impl FnOnce() -> R) -> R {
+    let prev = OBSERVER.with(|o| o.take());
+    let _restore = Restore::new(|| OBSERVER.with(|o| *o.borrow_mut() = prev));
+    f()
+}
+
+// ── Signal ────────────────────────────────────────────────────────────────────
+
+/// A [`Signal::subscribe`] callback. Shared between the signal and any
+/// queued notification, so removing it never frees a running callback.
+struct SubscriberEntry {
+    f: Box<dyn Fn()>,
+    active: Cell<bool>,
+    /// Already waiting in the queue, so further writes needn't add it again.
+    queued: Cell<bool>,
+}
+
+type Subscriber = Rc<SubscriberEntry>;
+
+struct SignalInner<T> {
+    value: T,
+    subscribers: Vec<Subscriber>,
+    observers: Vec<Rc<Computation>>,
+    /// 0 for plain state; a memo's output carries the memo's height.
+    height: usize,
+}
+
+impl<T> Source for RefCell<SignalInner<T>> {
+    fn unlink(&self, observer: &Rc<Computation>) {
+        self.borrow_mut()
+            .observers
+            .retain(|o| !Rc::ptr_eq(o, observer));
+    }
+}
+
+/// Reactive value. Cloning shares the same underlying state.
+pub struct Signal<T: Clone + 'static> {
+    inner: Rc<RefCell<SignalInner<T>>>,
+}
+
+impl<T: Clone + 'static> Clone for Signal<T> {
+    fn clone(&self) -> Self {
+        Signal {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: Clone + 'static> Signal<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Signal {
+            inner: Rc::new(RefCell::new(SignalInner {
+                value,
+                subscribers: Vec::new(),
+                observers: Vec::new(),
+                height: 0,
+            })),
+        }
+    }
+
+    /// Returns the current value (cloned).
+    ///
+    /// Inside [`use_memo`] or [`create_effect`] this also registers the signal
+    /// as a dependency.
+    pub fn get(&self) -> T {
+        self.track();
+        self.inner.borrow().value.clone()
+    }
+
+    /// Updates the value and notifies all subscribers.
+    ///
+    /// Dependent memos, effects and subscribers run before `set` returns,
+    /// unless called inside [`batch`] or from one of those callbacks, in
+    /// which case they run once the outer update finishes.
+    ///
+    /// Calling `set`, `subscribe` or dropping a [`Subscription`] from inside
+    /// a subscriber is fine: subscribers added mid-update first run on the
+    /// next change, and removed ones that were still queued are skipped.
+    pub fn set(&self, value: T) {
+        // Drop the old value outside the borrow; its destructor may touch signals.
+        let old = std::mem::replace(&mut self.inner.borrow_mut().value, value);
+        drop(old);
+        {
+            let inner = self.inner.borrow();
+            QUEUE.with(|q| {
+                let mut q = q.borrow_mut();
+                for observer in &inner.observers {
+                    q.push_computation(observer);
+                }
+                for f in &inner.subscribers {
+                    q.push_subscriber(f);
+                }
+            });
+        }
+        if BATCH_DEPTH.with(Cell::get) == 0 {
+            flush();
+        }
+    }
+
+    /// Registers a callback that runs on every value change.
+    ///
+    /// The callback is removed when the returned [`Subscription`] is dropped;
+    /// call [`Subscription::forget`] to keep it for the lifetime of the
+    /// current [`Scope`].
+    pub fn subscribe<F: Fn() + 'static>(&self, f: F) -> Subscription {
+        let entry = Rc::new(SubscriberEntry {
+            f: Box::new(f),
+            active: Cell::new(true),
+            queued: Cell::new(false),
+        });
+        self.inner.borrow_mut().subscribers.push(Rc::clone(&entry));
+        let signal = Rc::downgrade(&self.inner);
+        Subscription {
+            unsubscribe: Some(Box::new(move || {
+                entry.active.set(false);
+                if let Some(signal) = signal.upgrade() {
+                    signal
+                        .borrow_mut()
+                        .subscribers
+                        .retain(|s| !Rc::ptr_eq(s, &entry));
+                }
+            })),
+        }
+    }
+
+    fn track(&self) {
+        let Some(observer) = OBSERVER.with(|o| o.borrow().clone()) else {
+            return;
+        };
+        let mut inner = self.inner.borrow_mut();
+        if inner.observers.iter().any(|o| Rc::ptr_eq(o, &observer)) {
+            return;
+        }
+        observer
+            .height
+            .set(observer.height.get().max(inner.height + 1));
+        inner.observers.push(Rc::clone(&observer));
+        let source: Weak<dyn Source> = Rc::downgrade(&self.inner) as Weak<dyn Source>;
+        observer.sources.borrow_mut().push(source);
+    }
+}
+
+/// Handle to a [`Signal::subscribe`] callback. Unsubscribes on drop.
+#[must_use = "the subscriber is removed as soon as the Subscription is dropped; call .forget() to keep it"]
+pub struct Subscription {
+    unsubscribe: Option<Box<dyn FnOnce()>>,
+}
+
+impl Subscription {
+    /// Keeps the subscriber until the current [`Scope`] is disposed, or for
+    /// good when called outside any scope.
+    pub fn forget(mut self) {
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            on_cleanup(unsubscribe);
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe();
+        }
+    }
+}
+
+impl<T: Clone + std::fmt::Display + 'static> std::fmt::Display for Signal<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.get())
+    }
+}
+
+/// Creates a reactive state value.
+pub fn use_state<T: Clone + 'static>(initial: T) -> Signal<T> {
+    Signal::new(initial)
+}
+
+// ── Memo & effects ────────────────────────────────────────────────────────────
+
+/// Computed signal that re-evaluates whenever a signal read by `compute` changes.
+///
+/// Dependencies are tracked automatically on every run, so there is nothing
+/// to list and conditional reads are handled:
+///
+/// ```ignore
+/// let total = use_memo(move || a.get() + b.get() + c.get() + d.get());
+/// ```
+///
+/// A recomputed value equal to the previous one is not written, so memos,
+/// effects and subscribers further down are only notified of real changes.
+pub fn use_memo<T, F>(compute: F) -> Signal<T>
+where
+    T: Clone + PartialEq + 'static,
+    F: Fn() -> T + 'static,
+{
+    let compute = Rc::new(compute);
+    let result: Rc<RefCell<Option<Signal<T>>>> = Rc::new(RefCell::new(None));
+    let result_run = Rc::clone(&result);
+    let compute_run = Rc::clone(&compute);
+    let computation = Computation::new(true, move || {
+        let value = compute_run();
+        let height = OBSERVER.with(|o| o.borrow().as_ref().map_or(0, |c| c.height.get()));
+        let existing = result_run.borrow().clone();
+        let signal = match existing {
+            Some(signal) => {
+                let changed = signal.inner.borrow().value != value;
+                if changed {
+                    signal.set(value);
+                }
+                signal
+            }
+            None => {
+                let signal = Signal::new(value);
+                *result_run.borrow_mut() = Some(signal.clone());
+                signal
+            }
+        };
+        signal.inner.borrow_mut().height = height;
+    });
+    // Writes made during the first run are queued like those of any other run.
+    batch(|| computation.run());
+    let signal = result.borrow().clone();
+    // Created in a disposed scope, the memo never runs; it keeps the value
+    // it would have started with.
+    signal.unwrap_or_else(|| Signal::new(untrack(|| compute())))
+}
+
+/// Runs `f` now and again whenever a signal it read changes.
+///
+/// Unlike [`use_effect`](crate::use_effect), which runs once on the next
+/// tick, this is the building block for keeping the DOM in sync with state.
+pub fn create_effect<F: Fn() + 'static>(f: F) {
+    let computation = Computation::new(false, f);
+    batch(|| computation.run());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counter() -> (Rc<Cell<usize>>, impl Fn() + Clone + 'static) {
+        let n = Rc::new(Cell::new(0));
+        let hit = {
+            let n = Rc::clone(&n);
+            move || n.set(n.get() + 1)
+        };
+        (n, hit)
+    }
+
+    #[test]
+    fn set_from_subscriber_settles_on_latest_value() {
+        let count = use_state(0);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let c = count.clone();
+        count
+            .subscribe(move || {
+                if c.get() < 3 {
+                    c.set(c.get() + 1);
+                }
+            })
+            .forget();
+        let (c, log) = (count.clone(), Rc::clone(&seen));
+        count
+            .subscribe(move || log.borrow_mut().push(c.get()))
+            .forget();
+
+        count.set(1);
+        assert_eq!(count.get(), 3);
+        // Every notification observes the value current at the time it runs.
+        assert_eq!(*seen.borrow(), vec![2, 3]);
+    }
+
+    #[test]
+    fn subscribe_from_subscriber_waits_for_next_change() {
+        let count = use_state(0);
+        let (late_hits, hit) = counter();
+        let added = Rc::new(Cell::new(false));
+
+        let (c, added_flag) = (count.clone(), Rc::clone(&added));
+        count
+            .subscribe(move || {
+                if !added_flag.replace(true) {
+                    // Enough pushes to force the subscriber Vec to reallocate.
+                    for _ in 0..64 {
+                        c.subscribe(|| {}).forget();
+                    }
+                    c.subscribe(hit.clone()).forget();
+                }
+            })
+            .forget();
+
+        count.set(1);
+        assert_eq!(late_hits.get(), 0);
+        count.set(2);
+        assert_eq!(late_hits.get(), 1);
+    }
+
+    #[test]
+    fn unsubscribe_self_from_callback() {
+        let count = use_state(0);
+        let (hits, hit) = counter();
+        let handle: Rc<RefCell<Option<Subscription>>> = Rc::new(RefCell::new(None));
+
+        let slot = Rc::clone(&handle);
+        let sub = count.subscribe(move || {
+            hit();
+            // Drops the subscription — and with it this very closure's entry.
+            slot.borrow_mut().take();
+        });
+        *handle.borrow_mut() = Some(sub);
+
+        count.set(1);
+        count.set(2);
+        assert_eq!(hits.get(), 1);
+    }
+
+    #[test]
+    fn unsubscribed_while_queued_is_skipped() {
+        let count = use_state(0);
+        let (hits, hit) = counter();
+        let victim: Rc<RefCell<Option<Subscription>>> = Rc::new(RefCell::new(None));
+
+        let slot = Rc::clone(&victim);
+        count
+            .subscribe(move || {
+                slot.borrow_mut().take();
+            })
+            .forget();
+        *victim.borrow_mut() = Some(count.subscribe(hit));
+
+        count.set(1);
+        assert_eq!(hits.get(), 0);
+    }
+
+    #[test]
+    fn set_other_signal_from_subscriber() {
+        let a = use_state(0);
+        let b = use_state(0);
+
+        let (a2, b2) = (a.clone(), b.clone());
+        a.subscribe(move || b2.set(a2.get() * 10)).forget();
+        let (a3, b3) = (a.clone(), b.clone());
+        // Writes back into `a` from `b`'s subscriber: still no RefCell panic.
+        b.subscribe(move || {
+            if b3.get() > 10 {
+                a3.set(1);
+            }
+        })
+        .forget();
+
+        a.set(5);
+        assert_eq!(a.get(), 1);
+        assert_eq!(b.get(), 10);
+    }
+
+    #[test]
+    fn effect_writing_its_own_dependency_converges() {
+        let count = use_state(0);
+        let (runs, hit) = counter();
+
+        let c = count.clone();
+        create_effect(move || {
+            hit();
+            if c.get() < 5 {
+                c.set(c.get() + 1);
+            }
+        });
+
+        assert_eq!(count.get(), 5);
+        assert_eq!(runs.get(), 6);
+    }
+
+    #[test]
+    fn dispose_scope_from_subscriber() {
+        let count = use_state(0);
+        let (runs, hit) = counter();
+
+        let scope = Scope::new();
+        let c = count.clone();
+        scope.run(|| {
+            create_effect(move || {
+                c.get();
+                hit();
+            })
+        });
+
+        let s = scope.clone();
+        count.subscribe(move || s.dispose()).forget();
+
+        count.set(1);
+        // Initial run plus the first write; then the scope went away.
+        assert_eq!(runs.get(), 2);
+        count.set(2);
+        assert_eq!(runs.get(), 2);
+    }
+
+    #[test]
+    fn memo_follows_branch_switch() {
+        let flag = use_state(true);
+        let a = use_state(1);
+        let b = use_state(2);
+        let (runs, hit) = counter();
+
+        let (f, a2, b2) = (flag.clone(), a.clone(), b.clone());
+        let picked = use_memo(move || {
+            hit();
+            if f.get() { a2.get() } else { b2.get() }
+        });
+        assert_eq!(runs.get(), 1);
+
+        b.set(20);
+        assert_eq!(runs.get(), 1);
+
+        flag.set(false);
+        assert_eq!((picked.get(), runs.get()), (20, 2));
+
+        // `a` was only read by the old branch.
+        a.set(10);
+        assert_eq!(runs.get(), 2);
+        b.set(30);
+        assert_eq!((picked.get(), runs.get()), (30, 3));
+    }
+
+    #[test]
+    fn memo_ignores_signals_it_did_not_read() {
+        let a = use_state(1);
+        let unrelated = use_state(0);
+        let (runs, hit) = counter();
+
+        let a2 = a.clone();
+        let doubled = use_memo(move || {
+            hit();
+            a2.get() * 2
+        });
+
+        unrelated.set(1);
+        assert_eq!(runs.get(), 1);
+        a.set(2);
+        assert_eq!((doubled.get(), runs.get()), (4, 2));
+    }
+
+    #[test]
+    fn memo_does_not_track_untracked_reads() {
+        let a = use_state(1);
+        let b = use_state(10);
+        let (runs, hit) = counter();
+
+        let (a2, b2) = (a.clone(), b.clone());
+        let sum = use_memo(move || {
+            hit();
+            a2.get() + untrack(|| b2.get())
+        });
+
+        b.set(20);
+        assert_eq!((sum.get(), runs.get()), (11, 1));
+        a.set(2);
+        assert_eq!((sum.get(), runs.get()), (22, 2));
+    }
+
+    #[test]
+    fn unchanged_memo_value_does_not_notify() {
+        let n = use_state(0);
+        let (memo_runs, memo_hit) = counter();
+        let (effect_runs, effect_hit) = counter();
+        let (sub_hits, sub_hit) = counter();
+
+        let n2 = n.clone();
+        let parity = use_memo(move || {
+            memo_hit();
+            n2.get() % 2
+        });
+        let p = parity.clone();
+        create_effect(move || {
+            p.get();
+            effect_hit();
+        });
+        parity.subscribe(sub_hit).forget();
+
+        n.set(2);
+        assert_eq!(memo_runs.get(), 2);
+        assert_eq!((effect_runs.get(), sub_hits.get()), (1, 0));
+
+        n.set(3);
+        assert_eq!((effect_runs.get(), sub_hits.get()), (2, 1));
+    }
+
+    #[test]
+    fn disposed_scope_stops_its_effects() {
+        let count = use_state(0);
+        let (runs, hit) = counter();
+
+        let scope = Scope::new();
+        let c = count.clone();
+        scope.run(|| {
+            create_effect(move || {
+                c.get();
+                hit();
+            })
+        });
+        count.set(1);
+        assert_eq!(runs.get(), 2);
+
+        scope.dispose();
+        count.set(2);
+        assert_eq!(runs.get(), 2);
+    }
+
+    #[test]
+    fn dispose_runs_children_then_cleanups_newest_first() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let push = |entry: &'static str| {
+            let log = Rc::clone(&log);
+            move || log.borrow_mut().push(entry)
+        };
+
+        let scope = Scope::new();
+        scope.run(|| {
+            on_cleanup(push("first"));
+            let child = Scope::new();
+            child.run(|| on_cleanup(push("child")));
+            on_cleanup(push("second"));
+        });
+
+        scope.dispose();
+        assert_eq!(*log.borrow(), ["child", "second", "first"]);
+        scope.dispose();
+        assert_eq!(log.borrow().len(), 3);
+    }
+
+    #[test]
+    fn disposing_parent_disposes_nested_scopes() {
+        let count = use_state(0);
+        let (runs, hit) = counter();
+
+        let parent = Scope::new();
+        let c = count.clone();
+        parent.run(|| {
+            let child = Scope::new();
+            child.run(|| {
+                let grandchild = Scope::new();
+                grandchild.run(|| {
+                    create_effect(move || {
+                        c.get();
+                        hit();
+                    })
+                });
+            });
+        });
+
+        parent.dispose();
+        count.set(1);
+        assert_eq!(runs.get(), 1);
+    }
+
+    #[test]
+    fn dropping_subscription_unsubscribes() {
+        let count = use_state(0);
+        let (hits, hit) = counter();
+
+        let sub = count.subscribe(hit);
+        count.set(1);
+        drop(sub);
+        count.set(2);
+        assert_eq!(hits.get(), 1);
+    }
+
+    #[test]
+    fn forgotten_subscription_lives_until_scope_is_disposed() {
+        let count = use_state(0);
+        let (hits, hit) = counter();
+
+        let scope = Scope::new();
+        scope.run(|| count.subscribe(hit).forget());
+        count.set(1);
+        assert_eq!(hits.get(), 1);
+
+        scope.dispose();
+        count.set(2);
+        assert_eq!(hits.get(), 1);
+    }
+
+    #[test]
+    fn diamond_settles_once_with_consistent_values() {
+        let a = use_state(1);
+        let a2 = a.clone();
+        let b = use_memo(move || a2.get() + 1);
+        let a3 = a.clone();
+        let c = use_memo(move || a3.get() * 2);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let log = Rc::clone(&seen);
+        create_effect(move || log.borrow_mut().push((b.get(), c.get())));
+
+        a.set(5);
+        assert_eq!(*seen.borrow(), [(2, 2), (6, 10)]);
+    }
+
+    #[test]
+    fn memo_of_memo_sees_settled_inputs() {
+        let a = use_state(1);
+        let (runs, hit) = counter();
+
+        let a2 = a.clone();
+        let doubled = use_memo(move || a2.get() * 2);
+        let a3 = a.clone();
+        // Reads `a` directly and through `doubled`, one level apart.
+        let tripled = use_memo(move || {
+            hit();
+            a3.get() + doubled.get()
+        });
+
+        a.set(4);
+        assert_eq!((tripled.get(), runs.get()), (12, 2));
+    }
+
+    #[test]
+    fn nested_batches_flush_once_at_the_outermost() {
+        let a = use_state(0);
+        let b = use_state(0);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let (a2, b2, log) = (a.clone(), b.clone(), Rc::clone(&seen));
+        create_effect(move || log.borrow_mut().push((a2.get(), b2.get())));
+
+        batch(|| {
+            a.set(1);
+            batch(|| b.set(2));
+            // The inner batch ended, but the outer one still holds the queue.
+            assert_eq!(seen.borrow().len(), 1);
+            a.set(3);
+        });
+        assert_eq!(*seen.borrow(), [(0, 0), (3, 2)]);
+    }
+
+    #[test]
+    fn effect_created_under_disposed_scope_never_runs() {
+        let count = use_state(0);
+        let (runs, hit) = counter();
+
+        let scope = Scope::new();
+        scope.dispose();
+        let c = count.clone();
+        scope.run(|| {
+            let child = Scope::new();
+            assert!(child.is_disposed());
+            child.run(|| {
+                create_effect(move || {
+                    c.get();
+                    hit();
+                })
+            });
+        });
+
+        count.set(1);
+        assert_eq!(runs.get(), 0);
+    }
+
+    #[test]
+    fn cleanup_in_disposed_scope_runs_immediately() {
+        let (runs, hit) = counter();
+        let count = use_state(0);
+        let (hits, sub_hit) = counter();
+
+        let scope = Scope::new();
+        scope.dispose();
+        scope.run(|| {
+            on_cleanup(hit);
+            count.subscribe(sub_hit).forget();
+        });
+        assert_eq!(runs.get(), 1);
+
+        count.set(1);
+        assert_eq!(hits.get(), 0);
+    }
+
+    #[test]
+    fn memo_created_under_disposed_scope_keeps_its_first_value() {
+        let count = use_state(1);
+
+        let scope = Scope::new();
+        scope.dispose();
+        let c = count.clone();
+        let doubled = scope.run(|| use_memo(move || c.get() * 2));
+
+        count.set(2);
+        assert_eq!(doubled.get(), 2);
+    }
+
+    #[test]
+    fn subscriber_runs_once_for_repeated_writes_in_a_batch() {
+        let count = use_state(0);
+        let (runs, hit) = counter();
+        count.subscribe(hit).forget();
+
+        batch(|| {
+            for n in 1..=3 {
+                count.set(n);
+            }
+        });
+        assert_eq!(runs.get(), 1);
+
+        count.set(4);
+        assert_eq!(runs.get(), 2);
+    }
+
+    #[test]
+    fn panicking_effect_does_not_stall_later_updates() {
+        let count = use_state(0);
+        let c = count.clone();
+        create_effect(move || assert_ne!(c.get(), 1, "effect failed"));
+
+        let set = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| count.set(1)));
+        assert!(set.is_err());
+        assert!(!has_owner());
+
+        let (runs, hit) = counter();
+        let c = count.clone();
+        create_effect(move || {
+            c.get();
+            hit();
+        });
+        count.set(2);
+        assert_eq!(runs.get(), 2);
+    }
+}
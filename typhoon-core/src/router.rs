@@ -0,0 +1,1606 @@
+//! Client-side routing over hash (`#/users/42`) or History API (`/users/42`) URLs.
+//!
+//! The current [`Location`] lives in one app-wide signal, fed by `popstate` /
+//! `hashchange` and by [`navigate`]. [`use_routes`] matches it against route
+//! patterns and swaps the rendered view whenever the match changes.
+//!
+//! Routes nest: a layout route renders an [`outlet`] where its matched child
+//! goes, and each level only re-renders when its own part of the match
+//! changes.
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Display,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+use web_sys::{DocumentFragment, Element, MouseEvent, Node};
+
+use crate::{
+    Scope, Signal, create_effect, create_element, create_fragment, listen, on, scroll, spawn_local,
+    switch_view, untrack, use_memo,
+};
+
+// ── Patterns ──────────────────────────────────────────────────────────────────
+
+/// Values captured by a route pattern, e.g. `id` in `/users/:id`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    /// `self` followed by `other`; on duplicate names the later value wins.
+    fn merged(&self, other: Params) -> Params {
+        let mut params = self.0.clone();
+        for (name, value) in other.0 {
+            params.retain(|(key, _)| *key != name);
+            params.push((name, value));
+        }
+        Params(params)
+    }
+
+    /// The raw (percent-decoded) value captured for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value captured for `name`, parsed with [`FromStr`].
+    ///
+    /// ```ignore
+    /// let id: u64 = params.parse("id")?;
+    /// ```
+    pub fn parse<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name)?.parse().ok()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Matches `path` against `pattern`, returning the captured parameters.
+///
+/// `:name` captures one segment and `*name` captures the rest of the path
+/// (possibly empty). Empty segments are ignored, so `/users/42/` matches
+/// `/users/:id`.
+///
+/// ```ignore
+/// let params = match_path("/files/*rest", "/files/a/b.txt").unwrap();
+/// assert_eq!(params.get("rest"), Some("a/b.txt"));
+/// ```
+pub fn match_path(pattern: &str, path: &str) -> Option<Params> {
+    let path: Vec<&str> = segments(path).collect();
+    match match_prefix(pattern, &path)? {
+        (params, []) => Some(params),
+        _ => None,
+    }
+}
+
+/// Matches the start of `path` against `pattern`, returning the captured
+/// parameters and the segments left over for nested routes.
+fn match_prefix<'a, 'p>(pattern: &str, path: &'a [&'p str]) -> Option<(Params, &'a [&'p str])> {
+    let mut params = Vec::new();
+    let mut rest = path;
+
+    for part in segments(pattern) {
+        if let Some(name) = part.strip_prefix('*') {
+            params.push((name.to_owned(), decode(&rest.join("/"))));
+            return Some((Params(params), &[]));
+        }
+        let (segment, tail) = rest.split_first()?;
+        if let Some(name) = part.strip_prefix(':') {
+            params.push((name.to_owned(), decode(segment)));
+        } else if part != *segment {
+            return None;
+        }
+        rest = tail;
+    }
+
+    Some((Params(params), rest))
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+/// Percent-encodes `value` for use as one path segment.
+#[doc(hidden)]
+pub fn __encode_segment(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => out.push(byte as char),
+            b'-' | b'.' | b'_' | b'~' | b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+'
+            | b',' | b';' | b'=' | b':' | b'@' => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+/// Percent-encodes each `/`-separated segment of `value`, keeping the slashes.
+#[doc(hidden)]
+pub fn __encode_path(value: &str) -> String {
+    value
+        .split('/')
+        .map(__encode_segment)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Percent-decodes a path segment; malformed escapes are kept as-is.
+pub(crate) fn decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// ── Router ────────────────────────────────────────────────────────────────────
+
+/// Which part of the URL holds the route path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RouterMode {
+    /// `index.html#/users/42` — works on any static host.
+    #[default]
+    Hash,
+    /// `/users/42` via `pushState` — the server must serve the app for every path.
+    History,
+}
+
+/// A view rendered once its future resolves. The future yields the render
+/// step rather than the view, so the view is still built inside its route's
+/// scope.
+type Pending = Pin<Box<dyn Future<Output = Deferred>>>;
+type Deferred = Box<dyn FnOnce(&Router) -> Node>;
+
+enum View {
+    Sync(Rc<dyn Fn(&Params) -> Element>),
+    Async(Rc<dyn Fn(&Params) -> Pending>),
+}
+
+type Check = Rc<dyn Fn(&Params) -> Guard>;
+type ErrorView = Rc<dyn Fn(&str) -> Element>;
+
+/// What a [`Router::guard`] decides about a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Guard {
+    /// Render the route as usual.
+    Allow,
+    /// Render the fallback view instead of the route.
+    Deny,
+    /// Replace the URL with this path.
+    Redirect(String),
+}
+
+struct Route {
+    pattern: String,
+    view: View,
+    /// Routes rendered into this route's [`outlet`], matched against the
+    /// rest of the path.
+    children: Vec<Route>,
+}
+
+/// The matched route at each nesting level, outermost first, as an index
+/// into that level's routes plus every parameter captured up to it.
+type Chain = Vec<(usize, Params)>;
+
+/// Route table for [`use_routes`].
+///
+/// ```ignore
+/// let app = use_routes(
+///     Router::history()
+///         .route("/", |_| home())
+///         .route("/users/:id", |p| user_page(p.parse("id").unwrap_or(0)))
+///         .route("/files/*path", |p| file_view(p.get("path").unwrap_or("")))
+///         .redirect("/u/:id", "/users/:id")
+///         .fallback(|| tp! { h1.text("Not found") }),
+/// );
+/// ```
+#[derive(Default)]
+pub struct Router {
+    mode: RouterMode,
+    routes: Vec<Route>,
+    redirects: Vec<(String, String)>,
+    guards: Vec<(String, Check)>,
+    fallback: Option<Rc<dyn Fn() -> Element>>,
+    pending: Option<Rc<dyn Fn() -> Element>>,
+    error: Option<ErrorView>,
+    canonicalize: bool,
+}
+
+/// What a [`Router::lazy`] view resolves to: an `Element`, or a `Result`
+/// whose error is shown with the router's [`error`](Router::error) view.
+pub trait LazyView {
+    fn into_view(self) -> Result<Element, String>;
+}
+
+impl LazyView for Element {
+    fn into_view(self) -> Result<Element, String> {
+        Ok(self)
+    }
+}
+
+impl<E: Display> LazyView for Result<Element, E> {
+    fn into_view(self) -> Result<Element, String> {
+        self.map_err(|e| e.to_string())
+    }
+}
+
+/// Where [`Router::resolve`] sends a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// A route matched. `pattern` is its full pattern, with the patterns of
+    /// enclosing layouts prepended.
+    Matched { pattern: String, params: Params },
+    /// The URL is replaced with this path, which is then resolved again.
+    Redirect(String),
+    /// A guard denied the path: the fallback view is shown, if there is one.
+    Denied,
+    /// Nothing matched: the fallback view is shown, if there is one.
+    NotFound,
+}
+
+/// How a path resolves, in terms the renderer can use directly.
+#[derive(Clone, Debug, PartialEq)]
+enum Resolved {
+    Routes(Chain),
+    Redirect(String),
+    Denied,
+    NotFound,
+}
+
+/// Redirect chains longer than this are treated as a loop and resolve to
+/// [`Resolution::NotFound`].
+const MAX_REDIRECTS: usize = 16;
+
+impl Router {
+    /// Routes on `location.hash`.
+    pub fn hash() -> Self {
+        Router {
+            mode: RouterMode::Hash,
+            ..Router::default()
+        }
+    }
+
+    /// Routes on `location.pathname`, navigating with `history.pushState`.
+    pub fn history() -> Self {
+        Router {
+            mode: RouterMode::History,
+            ..Router::default()
+        }
+    }
+
+    /// Adds a route. Routes are tried in order; the first match wins.
+    pub fn route<F>(mut self, pattern: &str, view: F) -> Self
+    where
+        F: Fn(&Params) -> Element + 'static,
+    {
+        self.routes.push(Route {
+            pattern: pattern.to_owned(),
+            view: View::Sync(Rc::new(view)),
+            children: Vec::new(),
+        });
+        self
+    }
+
+    /// Adds a route whose data is loaded before it renders: `loader` starts
+    /// when the route is entered, the [`pending`](Router::pending) view is
+    /// shown until it resolves, and then `view` renders the result.
+    ///
+    /// If the user moves on first, the loader is dropped and its result never
+    /// shown.
+    ///
+    /// ```ignore
+    /// Router::history().load(
+    ///     "/users/:id",
+    ///     |p| fetch_user(p.parse("id").unwrap_or(0)),
+    ///     |user: User| tp! { h1.text(&user.name) },
+    /// )
+    /// ```
+    pub fn load<T, L, Fut, F>(mut self, pattern: &str, loader: L, view: F) -> Self
+    where
+        T: 'static,
+        L: Fn(&Params) -> Fut + 'static,
+        Fut: Future<Output = T> + 'static,
+        F: Fn(T) -> Element + 'static,
+    {
+        let view = Rc::new(view);
+        self.routes.push(Route {
+            pattern: pattern.to_owned(),
+            view: View::Async(Rc::new(move |params| {
+                let data = loader(params);
+                let view = Rc::clone(&view);
+                Box::pin(async move {
+                    let data = data.await;
+                    Box::new(move |_: &Router| view(data).into()) as Deferred
+                })
+            })),
+            children: Vec::new(),
+        });
+        self
+    }
+
+    /// Adds a route whose view is built asynchronously, so it can be fetched
+    /// or initialised on demand rather than up front. The
+    /// [`pending`](Router::pending) view is shown until it is ready.
+    ///
+    /// The future resolves to an `Element`, or to a `Result` whose error is
+    /// shown with the [`error`](Router::error) view. Effects and listeners it
+    /// sets up belong to the route, like those of any other view.
+    ///
+    /// ```ignore
+    /// Router::history().lazy("/reports", |_| async {
+    ///     let data = fetch_reports().await?;
+    ///     Ok::<_, FetchError>(reports_page(data))
+    /// })
+    /// ```
+    pub fn lazy<F, Fut, V>(mut self, pattern: &str, view: F) -> Self
+    where
+        F: Fn(&Params) -> Fut + 'static,
+        Fut: Future<Output = V> + 'static,
+        V: LazyView,
+    {
+        self.routes.push(Route {
+            pattern: pattern.to_owned(),
+            view: View::Async(Rc::new(move |params| {
+                let view = view(params);
+                Box::pin(async move {
+                    let view = view.await.into_view();
+                    Box::new(move |router: &Router| match view {
+                        Ok(el) => el.into(),
+                        Err(message) => router.error_view(&message),
+                    }) as Deferred
+                })
+            })),
+            children: Vec::new(),
+        });
+        self
+    }
+
+    /// Shown while a [`load`](Router::load) or [`lazy`](Router::lazy) route
+    /// is loading. Without one, nothing is rendered in the meantime.
+    pub fn pending<F>(mut self, view: F) -> Self
+    where
+        F: Fn() -> Element + 'static,
+    {
+        self.pending = Some(Rc::new(view));
+        self
+    }
+
+    /// Adds a layout route whose `children` are matched against the rest of
+    /// the path and rendered where `layout` calls [`outlet`].
+    ///
+    /// Moving between children only re-renders the outlet; the layout keeps
+    /// its DOM and state. A layout only matches when one of its children does,
+    /// so give it a `"/"` child for the bare prefix.
+    ///
+    /// ```ignore
+    /// Router::history().nest(
+    ///     "/settings",
+    ///     |_| tp! { div { (settings_nav()) (outlet()) } },
+    ///     |r| r.route("/", |_| overview()).route("/profile", |_| profile()),
+    /// )
+    /// ```
+    pub fn nest<F, C>(mut self, pattern: &str, layout: F, children: C) -> Self
+    where
+        F: Fn(&Params) -> Element + 'static,
+        C: FnOnce(Router) -> Router,
+    {
+        self.routes.push(Route {
+            pattern: pattern.to_owned(),
+            view: View::Sync(Rc::new(layout)),
+            children: children(Router::default()).routes,
+        });
+        self
+    }
+
+    /// Sends paths matching `from` to `to`, replacing the URL. Parameters
+    /// captured by `from` can be used in `to`:
+    /// `.redirect("/u/:id", "/users/:id")`.
+    ///
+    /// Redirects are checked before routes, in the order they were added.
+    pub fn redirect(mut self, from: &str, to: &str) -> Self {
+        self.redirects.push((from.to_owned(), to.to_owned()));
+        self
+    }
+
+    /// Shown when a [`lazy`](Router::lazy) view fails, with the error
+    /// message. Without one, the error is logged to the console.
+    pub fn error<F>(mut self, view: F) -> Self
+    where
+        F: Fn(&str) -> Element + 'static,
+    {
+        self.error = Some(Rc::new(view));
+        self
+    }
+
+    fn error_view(&self, message: &str) -> Node {
+        match &self.error {
+            Some(view) => view(message).into(),
+            None => {
+                web_sys::console::error_1(&message.into());
+                create_fragment().into()
+            }
+        }
+    }
+
+    /// Checks every path under `pattern` before it renders. `"/admin"` covers
+    /// `/admin` and everything below it; `"/"` covers the whole app.
+    ///
+    /// Signals read by `check` are tracked, so signing out while on a guarded
+    /// page re-runs it. Guards run in the order they were added, after
+    /// redirects, and the first one that doesn't [`Allow`](Guard::Allow) wins.
+    ///
+    /// ```ignore
+    /// Router::history()
+    ///     .route("/login", |_| login())
+    ///     .nest("/admin", |_| admin_layout(), |r| r.route("/", |_| dashboard()))
+    ///     .guard("/admin", move |_| match user.get() {
+    ///         Some(_) => Guard::Allow,
+    ///         None => Guard::Redirect("/login".into()),
+    ///     })
+    /// ```
+    pub fn guard<F>(mut self, pattern: &str, check: F) -> Self
+    where
+        F: Fn(&Params) -> Guard + 'static,
+    {
+        self.guards.push((pattern.to_owned(), Rc::new(check)));
+        self
+    }
+
+    /// Shown when no route matches or a guard denies the path. Without one,
+    /// nothing is rendered.
+    pub fn fallback<F>(mut self, view: F) -> Self
+    where
+        F: Fn() -> Element + 'static,
+    {
+        self.fallback = Some(Rc::new(view));
+        self
+    }
+
+    /// Redirects non-canonical paths (`//users/42/`) to their canonical form
+    /// (`/users/42`): no repeated or trailing slashes.
+    pub fn canonicalize(mut self) -> Self {
+        self.canonicalize = true;
+        self
+    }
+
+    /// Works out what the router does with `path`, without touching the DOM.
+    ///
+    /// ```ignore
+    /// let router = Router::history().route("/users/:id", |_| user());
+    /// assert_eq!(router.resolve("/nope"), Resolution::NotFound);
+    /// ```
+    pub fn resolve(&self, path: &str) -> Resolution {
+        match self.resolve_chain(path) {
+            Resolved::Routes(chain) => {
+                let indices: Vec<usize> = chain.iter().map(|(i, _)| *i).collect();
+                let mut pattern = String::new();
+                for depth in 1..=indices.len() {
+                    for segment in segments(&self.route_at(&indices[..depth]).pattern) {
+                        pattern.push('/');
+                        pattern.push_str(segment);
+                    }
+                }
+                if pattern.is_empty() {
+                    pattern.push('/');
+                }
+                let (_, params) = chain.into_iter().last().expect("empty route chain");
+                Resolution::Matched { pattern, params }
+            }
+            Resolved::Redirect(to) => Resolution::Redirect(to),
+            Resolved::Denied => Resolution::Denied,
+            Resolved::NotFound => Resolution::NotFound,
+        }
+    }
+
+    fn resolve_chain(&self, path: &str) -> Resolved {
+        let mut current = path.to_owned();
+        for hops in 0..=MAX_REDIRECTS {
+            let canonical = canonical_path(&current);
+            let next = if self.canonicalize && canonical != current {
+                Some(canonical)
+            } else if let Some(to) = self.redirect_target(&current) {
+                Some(to)
+            } else {
+                match self.check(&current) {
+                    Guard::Allow => None,
+                    Guard::Redirect(to) => Some(to),
+                    // Denied only once the URL has been updated.
+                    Guard::Deny if hops > 0 => None,
+                    Guard::Deny => return Resolved::Denied,
+                }
+            };
+            match next {
+                Some(next) if hops < MAX_REDIRECTS => current = next,
+                Some(_) => break,
+                None if hops > 0 => return Resolved::Redirect(current),
+                None => {
+                    return self
+                        .find(&current)
+                        .map_or(Resolved::NotFound, Resolved::Routes);
+                }
+            }
+        }
+        Resolved::NotFound
+    }
+
+    fn redirect_target(&self, path: &str) -> Option<String> {
+        self.redirects.iter().find_map(|(from, to)| {
+            let params = match_path(from, path)?;
+            Some(fill_pattern(to, &params))
+        })
+    }
+
+    fn check(&self, path: &str) -> Guard {
+        let path: Vec<&str> = segments(path).collect();
+        self.guards
+            .iter()
+            .filter_map(|(pattern, check)| {
+                let (params, _) = match_prefix(pattern, &path)?;
+                Some(check(&params))
+            })
+            .find(|guard| *guard != Guard::Allow)
+            .unwrap_or(Guard::Allow)
+    }
+
+    /// The chain of routes matching `path`, if any.
+    fn find(&self, path: &str) -> Option<Chain> {
+        let path: Vec<&str> = segments(path).collect();
+        match_routes(&self.routes, &path, &Params::default())
+    }
+
+    /// The route reached by following `indices` down from the top level.
+    fn route_at(&self, indices: &[usize]) -> &Route {
+        let (first, rest) = indices.split_first().expect("empty route chain");
+        rest.iter()
+            .fold(&self.routes[*first], |route, &i| &route.children[i])
+    }
+}
+
+fn match_routes(routes: &[Route], path: &[&str], inherited: &Params) -> Option<Chain> {
+    routes.iter().enumerate().find_map(|(i, route)| {
+        let (params, rest) = match_prefix(&route.pattern, path)?;
+        let params = inherited.merged(params);
+        if route.children.is_empty() {
+            return rest.is_empty().then(|| vec![(i, params)]);
+        }
+        let mut chain = match_routes(&route.children, rest, &params)?;
+        chain.insert(0, (i, params));
+        Some(chain)
+    })
+}
+
+/// `path` without repeated or trailing slashes, always starting with `/`.
+fn canonical_path(path: &str) -> String {
+    let mut canonical = String::with_capacity(path.len());
+    for segment in segments(path) {
+        canonical.push('/');
+        canonical.push_str(segment);
+    }
+    if canonical.is_empty() {
+        canonical.push('/');
+    }
+    canonical
+}
+
+/// Substitutes `:name` / `*name` segments of `pattern` with `params`.
+fn fill_pattern(pattern: &str, params: &Params) -> String {
+    let mut path = String::new();
+    for part in segments(pattern) {
+        let value = if let Some(name) = part.strip_prefix(':') {
+            __encode_segment(params.get(name).unwrap_or_default())
+        } else if let Some(name) = part.strip_prefix('*') {
+            __encode_path(params.get(name).unwrap_or_default())
+        } else {
+            part.to_owned()
+        };
+        if !value.is_empty() {
+            path.push('/');
+            path.push_str(&value);
+        }
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+    path
+}
+
+type RouteHandler = Box<dyn Fn() -> Element + 'static>;
+
+/// The [`use_router`] list as a [`Router`]: hash routes matched exactly. Add
+/// a [`fallback`](Router::fallback) for paths that match none of them.
+impl From<Vec<(&'static str, RouteHandler)>> for Router {
+    fn from(routes: Vec<(&'static str, RouteHandler)>) -> Self {
+        let mut router = Router::hash();
+        for (pattern, handler) in routes {
+            router = router.route(pattern.trim_start_matches('#'), move |_| handler());
+        }
+        router
+    }
+}
+
+/// Renders the route matching the current URL into a container element.
+///
+/// The view is re-rendered when the matched route or its parameters change,
+/// each time in a fresh [`Scope`](crate::Scope) so the old view's effects and
+/// listeners are torn down. Redirects replace the URL (keeping its query and
+/// hash); when no route matches, the router's fallback is shown, or nothing.
+pub fn use_routes(router: Router) -> Element {
+    let router = Rc::new(router);
+    start(router.mode);
+
+    let container = create_element("div");
+    let location = use_location();
+    let matcher = Rc::clone(&router);
+    let path = location.clone();
+    let resolved = use_memo(move || matcher.resolve_chain(&path.get().path));
+
+    let redirect = resolved.clone();
+    create_effect(move || {
+        if let Resolved::Redirect(to) = redirect.get() {
+            let current = untrack(|| location.get());
+            let url = Location {
+                path: to,
+                ..current
+            }
+            .to_url();
+            untrack(|| navigate_replace(&url));
+        }
+    });
+
+    render_level(
+        &container,
+        Outlet {
+            router,
+            resolved,
+            level: 0,
+        },
+    );
+    container
+}
+
+/// Hash router over a plain list of routes, e.g.
+/// `use_router(vec![("#/", Box::new(home)), ("#/about", Box::new(about))])`.
+///
+/// Paths are matched exactly against `location.hash`. For patterns, nesting,
+/// History API URLs and the rest, build a [`Router`] and use [`use_routes`].
+pub fn use_router(routes: Vec<(&'static str, RouteHandler)>) -> Element {
+    use_routes(Router::from(routes))
+}
+
+/// Where an [`outlet`] renders: one nesting level of a router.
+#[derive(Clone)]
+struct Outlet {
+    router: Rc<Router>,
+    resolved: Signal<Resolved>,
+    level: usize,
+}
+
+/// What one nesting level shows.
+#[derive(Clone, PartialEq)]
+enum LevelView {
+    /// The route at these indices, with the parameters it sees.
+    Route(Vec<usize>, Params),
+    Fallback,
+}
+
+thread_local! {
+    /// The outlet a layout being rendered right now should fill.
+    static OUTLETS: RefCell<Vec<Outlet>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Renders the route matched at `outlet.level` into `parent`, re-rendering
+/// only when that route or the parameters it sees change.
+fn render_level(parent: &Node, outlet: Outlet) {
+    let Outlet {
+        router,
+        resolved,
+        level,
+    } = outlet.clone();
+    switch_view(
+        parent,
+        move || match resolved.get() {
+            Resolved::Routes(chain) => {
+                let (_, params) = chain.get(level)?;
+                let indices = chain[..=level].iter().map(|(i, _)| *i).collect();
+                Some(LevelView::Route(indices, params.clone()))
+            }
+            Resolved::Denied | Resolved::NotFound if level == 0 => Some(LevelView::Fallback),
+            // Nothing while a redirect is on its way.
+            _ => None,
+        },
+        move |matched| -> Node {
+            let (indices, params) = match matched {
+                Some(LevelView::Route(indices, params)) => (indices, params),
+                Some(LevelView::Fallback) => {
+                    return match &router.fallback {
+                        Some(fallback) => fallback().into(),
+                        None => create_fragment().into(),
+                    };
+                }
+                None => return create_fragment().into(),
+            };
+            let view = match &router.route_at(&indices).view {
+                View::Sync(view) => Rc::clone(view),
+                View::Async(load) => return suspend(load(&params), Rc::clone(&router)),
+            };
+            OUTLETS.with(|o| {
+                o.borrow_mut().push(Outlet {
+                    level: level + 1,
+                    ..outlet.clone()
+                })
+            });
+            let el = view(&params);
+            OUTLETS.with(|o| o.borrow_mut().pop());
+            el.into()
+        },
+    );
+}
+
+/// Shows the router's pending view until `future` resolves, then the view it
+/// resolves to.
+///
+/// Call it inside the route's scope: the future is polled in a child scope,
+/// so whatever it sets up is torn down with the route. If the route goes away
+/// first, the future is dropped at its next wake-up and its result is never
+/// shown.
+fn suspend(future: Pending, router: Rc<Router>) -> Node {
+    let future = InScope::new(future);
+    let fragment = create_fragment();
+    let done = Signal::new(false);
+    let result: Rc<RefCell<Option<Deferred>>> = Rc::default();
+
+    let ready = done.clone();
+    let deferred = Rc::clone(&result);
+    switch_view(
+        &fragment,
+        move || ready.get(),
+        move |ready| -> Node {
+            if ready && let Some(render) = deferred.borrow_mut().take() {
+                return render(&router);
+            }
+            match &router.pending {
+                Some(pending) => pending().into(),
+                None => create_fragment().into(),
+            }
+        },
+    );
+
+    spawn_local(async move {
+        if let Some(render) = future.await {
+            *result.borrow_mut() = Some(render);
+            done.set(true);
+        }
+    });
+    fragment.into()
+}
+
+/// A future whose every poll runs with `scope` as the owner. Once the scope
+/// is disposed, the inner future is dropped and this resolves to `None`.
+struct InScope {
+    scope: Scope,
+    future: Option<Pending>,
+}
+
+impl InScope {
+    /// Wraps `future` in a child of the current scope.
+    fn new(future: Pending) -> Self {
+        InScope {
+            scope: Scope::new(),
+            future: Some(future),
+        }
+    }
+}
+
+impl Future for InScope {
+    type Output = Option<Deferred>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Deferred>> {
+        let InScope { scope, future } = &mut *self;
+        let polled = match future {
+            Some(future) if !scope.is_disposed() => scope.run(|| future.as_mut().poll(cx)),
+            _ => Poll::Pending,
+        };
+        // Checked after polling too, in case the future navigated away itself.
+        if scope.is_disposed() {
+            *future = None;
+            return Poll::Ready(None);
+        }
+        polled.map(Some)
+    }
+}
+
+/// Marks where a layout route renders its matched child route.
+///
+/// Call it while rendering a view passed to [`Router::nest`]; anywhere else
+/// it renders nothing.
+///
+/// ```ignore
+/// |_| tp! { div.class("layout") { aside { (sidebar()) } main { (outlet()) } } }
+/// ```
+pub fn outlet() -> DocumentFragment {
+    let fragment = create_fragment();
+    if let Some(outlet) = OUTLETS.with(|o| o.borrow().last().cloned()) {
+        render_level(&fragment, outlet);
+    }
+    fragment
+}
+
+// ── Typed routes ──────────────────────────────────────────────────────────────
+
+/// A route enum that converts to and from URL paths.
+///
+/// Derive it with `#[derive(Routable)]`: each variant names its pattern with
+/// `#[at("/post/:id")]`, and its fields receive the captured parameters
+/// (parsed with `FromStr`, formatted with `Display`). One unit variant may be
+/// marked `#[not_found]` to catch every other path.
+///
+/// ```ignore
+/// #[derive(Clone, PartialEq, Routable)]
+/// enum Route {
+///     #[at("/")]
+///     Home,
+///     #[at("/post/:id")]
+///     Post { id: u64 },
+///     #[not_found]
+///     NotFound,
+/// }
+///
+/// assert_eq!(Route::from_path("/post/7"), Some(Route::Post { id: 7 }));
+/// assert_eq!(Route::Post { id: 7 }.to_path(), "/post/7");
+/// ```
+pub trait Routable: Clone + PartialEq + Sized + 'static {
+    /// Parses a route path such as `/post/7`.
+    fn from_path(path: &str) -> Option<Self>;
+
+    /// Formats the route as a path. A `#[not_found]` variant without an
+    /// `#[at]` pattern formats as `/`.
+    fn to_path(&self) -> String;
+}
+
+/// Like [`use_routes`], with routes parsed into `R` and rendered by `render`.
+///
+/// The view is re-rendered whenever the parsed route changes.
+///
+/// ```ignore
+/// use_typed_router(RouterMode::History, |route: Route| match route {
+///     Route::Home => home(),
+///     Route::Post { id } => post_page(id),
+///     Route::NotFound => tp! { h1.text("Not found") },
+/// })
+/// ```
+pub fn use_typed_router<R, F>(mode: RouterMode, render: F) -> Element
+where
+    R: Routable,
+    F: Fn(R) -> Element + 'static,
+{
+    start(mode);
+
+    let container = create_element("div");
+    let location = use_location();
+    switch_view(
+        &container,
+        move || R::from_path(&location.get().path),
+        move |route| -> Node {
+            match route {
+                Some(route) => render(route).into(),
+                None => create_fragment().into(),
+            }
+        },
+    );
+    container
+}
+
+/// Navigates to a typed route; see [`navigate`].
+pub fn navigate_to<R: Routable>(route: &R) {
+    navigate(&route.to_path());
+}
+
+// ── Location ──────────────────────────────────────────────────────────────────
+
+/// The parts of the URL the router cares about.
+///
+/// In hash mode they are read from the fragment — `#/users?tab=posts#bio`
+/// gives path `/users`, query `tab=posts` and hash `bio` — so both modes look
+/// the same to the app.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    /// Always starts with `/`.
+    pub path: String,
+    /// Without the leading `?`.
+    pub query: String,
+    /// Without the leading `#`.
+    pub hash: String,
+}
+
+impl Location {
+    /// Splits a `path?query#hash` string.
+    fn parse(url: &str) -> Location {
+        let (rest, hash) = url.split_once('#').unwrap_or((url, ""));
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let path = if path.starts_with('/') {
+            path.to_owned()
+        } else {
+            format!("/{path}")
+        };
+        Location {
+            path,
+            query: query.to_owned(),
+            hash: hash.to_owned(),
+        }
+    }
+
+    /// Joins the parts back into a `path?query#hash` string for [`navigate`].
+    pub(crate) fn to_url(&self) -> String {
+        let mut url = self.path.clone();
+        if !self.query.is_empty() {
+            url.push('?');
+            url.push_str(&self.query);
+        }
+        if !self.hash.is_empty() {
+            url.push('#');
+            url.push_str(&self.hash);
+        }
+        url
+    }
+}
+
+thread_local! {
+    static MODE: Cell<RouterMode> = const { Cell::new(RouterMode::Hash) };
+    static LOCATION: RefCell<Option<Signal<Location>>> = const { RefCell::new(None) };
+}
+
+/// The current [`Location`], kept in sync with the browser URL.
+///
+/// The routers render from this same signal. Treat it as read-only: change
+/// the URL with [`navigate`] and friends instead of setting it.
+///
+/// ```ignore
+/// let location = use_location();
+/// tp! { p.text(move || format!("You are at {}", location.get().path)) }
+/// ```
+pub fn use_location() -> Signal<Location> {
+    if let Some(location) = LOCATION.with(|l| l.borrow().clone()) {
+        return location;
+    }
+    let location = Signal::new(read_location());
+    LOCATION.with(|l| *l.borrow_mut() = Some(location.clone()));
+
+    // These live as long as the page; they are leaked on purpose rather than
+    // tied to whichever scope happened to ask for the location first.
+    let window = web_sys::window().expect("no window");
+    std::mem::forget(listen(&window, "popstate", |_: web_sys::PopStateEvent| {
+        refresh();
+        scroll::traversed(&untrack(|| use_location().get()).hash);
+    }));
+    std::mem::forget(listen(
+        &window,
+        "hashchange",
+        |_: web_sys::HashChangeEvent| refresh(),
+    ));
+    location
+}
+
+/// Reads the browser URL for the current mode.
+fn read_location() -> Location {
+    let Some(location) = web_sys::window().map(|w| w.location()) else {
+        return Location::parse("/");
+    };
+    match MODE.with(Cell::get) {
+        RouterMode::Hash => {
+            let hash = location.hash().unwrap_or_default();
+            Location::parse(hash.strip_prefix('#').unwrap_or(&hash))
+        }
+        RouterMode::History => Location {
+            path: location.pathname().unwrap_or_default(),
+            query: location
+                .search()
+                .unwrap_or_default()
+                .trim_start_matches('?')
+                .to_owned(),
+            hash: location
+                .hash()
+                .unwrap_or_default()
+                .trim_start_matches('#')
+                .to_owned(),
+        },
+    }
+}
+
+/// Sets a router up: switches which part of the URL routes are read from
+/// and takes over scroll restoration.
+fn start(mode: RouterMode) {
+    scroll::install();
+    if MODE.with(|m| m.replace(mode)) == mode {
+        refresh();
+    } else {
+        // Notify even if the location reads the same: links built before the
+        // router need their `href` redone for the new mode.
+        use_location().set(read_location());
+    }
+}
+
+/// Syncs the location signal with the URL, notifying only on an actual change.
+fn refresh() {
+    let location = use_location();
+    let next = read_location();
+    if untrack(|| location.get()) != next {
+        location.set(next);
+    }
+}
+
+pub(crate) fn history() -> web_sys::History {
+    web_sys::window()
+        .expect("no window")
+        .history()
+        .expect("no history")
+}
+
+/// Navigates to `path` (which may carry `?query` and `#hash`) without
+/// reloading the page.
+///
+/// In history mode this pushes a new history entry; in hash mode it sets
+/// `location.hash`. The new view starts scrolled to the top, or to the
+/// element named by the URL's `#hash`.
+///
+/// ```ignore
+/// button.onclick(|| navigate("/users/42"))
+/// ```
+pub fn navigate(path: &str) {
+    match MODE.with(Cell::get) {
+        RouterMode::Hash => {
+            let state = scroll::push_state();
+            web_sys::window()
+                .expect("no window")
+                .location()
+                .set_hash(path)
+                .expect("failed to set location.hash");
+            history().replace_state(&state, "").ok();
+        }
+        RouterMode::History => {
+            history()
+                .push_state_with_url(&scroll::push_state(), "", Some(path))
+                .expect("failed to push history state");
+        }
+    }
+    refresh();
+    scroll::navigated(&untrack(|| use_location().get()).hash);
+}
+
+/// Like [`navigate`], but replaces the current history entry instead of
+/// adding one, so Back skips it. The scroll position is left alone.
+pub fn navigate_replace(path: &str) {
+    match MODE.with(Cell::get) {
+        RouterMode::Hash => {
+            web_sys::window()
+                .expect("no window")
+                .location()
+                .replace(&format!("#{path}"))
+                .expect("failed to replace location");
+            history().replace_state(&scroll::replace_state(), "").ok();
+        }
+        RouterMode::History => {
+            history()
+                .replace_state_with_url(&scroll::replace_state(), "", Some(path))
+                .expect("failed to replace history state");
+        }
+    }
+    refresh();
+}
+
+/// Makes `el` (normally an `a`) a link to `path` handled by the router.
+///
+/// `href` is set for the current router mode, so the link can still be
+/// copied or opened in a new tab. Plain left-clicks call [`navigate`];
+/// clicks with a modifier key or another button, and links with a `target`,
+/// are left to the browser.
+///
+/// While the current path is `path` or below it, `el` gets the `active`
+/// class (`/` only counts as active on itself), plus `aria-current="page"`
+/// on an exact match. `tp!`'s `a.to(path)` calls this.
+///
+/// ```ignore
+/// let about = tp! { a { "About" } };
+/// link(&about, "/about");
+/// ```
+pub fn link(el: &Element, path: &str) {
+    let target = Location::parse(path).path;
+    let location = use_location();
+    let anchor = el.clone();
+    let url = path.to_owned();
+    create_effect(move || {
+        let current = location.get().path;
+        anchor
+            .set_attribute("href", &href(MODE.with(Cell::get), &url))
+            .expect("failed to set href");
+        anchor
+            .class_list()
+            .toggle_with_force("active", is_active(&target, &current))
+            .expect("failed to toggle active class");
+        if segments(&target).eq(segments(&current)) {
+            anchor
+                .set_attribute("aria-current", "page")
+                .expect("failed to set aria-current");
+        } else {
+            anchor.remove_attribute("aria-current").ok();
+        }
+    });
+
+    let path = path.to_owned();
+    let anchor = el.clone();
+    on(el, "click", move |e: MouseEvent| {
+        let modified = e.meta_key() || e.ctrl_key() || e.shift_key() || e.alt_key();
+        if e.default_prevented() || e.button() != 0 || modified || anchor.has_attribute("target") {
+            return;
+        }
+        e.prevent_default();
+        navigate(&path);
+    });
+}
+
+/// The `href` that leads to `path` in `mode`.
+fn href(mode: RouterMode, path: &str) -> String {
+    match mode {
+        RouterMode::Hash => format!("#{path}"),
+        RouterMode::History => path.to_owned(),
+    }
+}
+
+/// Whether a link to `target` counts as active at `current`.
+fn is_active(target: &str, current: &str) -> bool {
+    let target: Vec<&str> = segments(target).collect();
+    let current: Vec<&str> = segments(current).collect();
+    if target.is_empty() {
+        current.is_empty()
+    } else {
+        current.starts_with(&target)
+    }
+}
+
+/// Goes one entry back in the browser history, like the Back button.
+///
+/// The location updates once the browser fires `popstate`.
+pub fn back() {
+    history().back().expect("failed to go back");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Waker;
+
+    use super::*;
+    use crate::use_state;
+
+    #[test]
+    fn static_segments_match_exactly() {
+        assert_eq!(match_path("/", "/"), Some(Params::default()));
+        assert_eq!(match_path("/about", "/about"), Some(Params::default()));
+        assert_eq!(match_path("/about/", "/about"), Some(Params::default()));
+        assert_eq!(match_path("/about", "/about/"), Some(Params::default()));
+        assert_eq!(match_path("/about", "/"), None);
+        assert_eq!(match_path("/", "/about"), None);
+        assert_eq!(match_path("/about", "/about/team"), None);
+    }
+
+    #[test]
+    fn named_segments_capture_one_segment() {
+        let params = match_path("/users/:id/posts/:post", "/users/42/posts/7").unwrap();
+        assert_eq!(params.get("id"), Some("42"));
+        assert_eq!(params.parse::<u64>("post"), Some(7));
+        assert_eq!(params.get("missing"), None);
+        assert_eq!(match_path("/users/:id", "/users"), None);
+        assert_eq!(match_path("/users/:id", "/users/42/extra"), None);
+    }
+
+    #[test]
+    fn wildcard_captures_the_rest() {
+        let params = match_path("/files/*path", "/files/a/b/c.txt").unwrap();
+        assert_eq!(params.get("path"), Some("a/b/c.txt"));
+        let params = match_path("/files/*path", "/files").unwrap();
+        assert_eq!(params.get("path"), Some(""));
+        assert_eq!(match_path("/files/*path", "/other/a"), None);
+    }
+
+    #[test]
+    fn captured_values_are_percent_decoded() {
+        let params = match_path("/users/:name", "/users/Jane%20Doe").unwrap();
+        assert_eq!(params.get("name"), Some("Jane Doe"));
+        let params = match_path("/q/:v", "/q/%E2%9C%93%zz").unwrap();
+        assert_eq!(params.get("v"), Some("✓%zz"));
+    }
+
+    fn route(pattern: &str) -> Route {
+        Route {
+            pattern: pattern.to_owned(),
+            view: View::Sync(Rc::new(|_| unreachable!())),
+            children: Vec::new(),
+        }
+    }
+
+    fn nested(pattern: &str, children: Vec<Route>) -> Route {
+        Route {
+            children,
+            ..route(pattern)
+        }
+    }
+
+    fn indices(router: &Router, path: &str) -> Option<Vec<usize>> {
+        let chain = router.find(path)?;
+        Some(chain.into_iter().map(|(i, _)| i).collect())
+    }
+
+    #[test]
+    fn nested_routes_match_the_rest_of_the_path() {
+        let router = Router {
+            mode: RouterMode::History,
+            routes: vec![
+                route("/"),
+                nested(
+                    "/settings",
+                    vec![route("/"), route("/profile"), route("/account")],
+                ),
+            ],
+            ..Router::default()
+        };
+        assert_eq!(indices(&router, "/"), Some(vec![0]));
+        assert_eq!(indices(&router, "/settings"), Some(vec![1, 0]));
+        assert_eq!(indices(&router, "/settings/profile"), Some(vec![1, 1]));
+        assert_eq!(indices(&router, "/settings/account/"), Some(vec![1, 2]));
+        // A layout only matches when one of its children does.
+        assert_eq!(indices(&router, "/settings/unknown"), None);
+    }
+
+    #[test]
+    fn nested_routes_inherit_parent_params() {
+        let router = Router {
+            mode: RouterMode::History,
+            routes: vec![nested(
+                "/users/:id",
+                vec![route("/"), route("/posts/:post")],
+            )],
+            ..Router::default()
+        };
+        let chain = router.find("/users/42/posts/7").unwrap();
+        assert_eq!(chain[0].1.get("id"), Some("42"));
+        assert_eq!(chain[0].1.get("post"), None);
+        assert_eq!(chain[1].1.get("id"), Some("42"));
+        assert_eq!(chain[1].1.get("post"), Some("7"));
+        assert!(std::ptr::eq(
+            router.route_at(&[0, 1]),
+            &router.routes[0].children[1]
+        ));
+    }
+
+    fn app() -> Router {
+        Router {
+            mode: RouterMode::History,
+            routes: vec![
+                route("/"),
+                route("/users/:id"),
+                nested("/settings", vec![route("/"), route("/profile")]),
+            ],
+            ..Router::default()
+        }
+    }
+
+    fn matched(pattern: &str, params: &[(&str, &str)]) -> Resolution {
+        Resolution::Matched {
+            pattern: pattern.to_owned(),
+            params: Params(
+                params
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn unmatched_paths_resolve_to_not_found() {
+        let router = app();
+        assert_eq!(router.resolve("/"), matched("/", &[]));
+        assert_eq!(
+            router.resolve("/users/42"),
+            matched("/users/:id", &[("id", "42")])
+        );
+        assert_eq!(
+            router.resolve("/settings/profile"),
+            matched("/settings/profile", &[])
+        );
+        assert_eq!(router.resolve("/settings"), matched("/settings", &[]));
+        assert_eq!(router.resolve("/nope"), Resolution::NotFound);
+        assert_eq!(router.resolve("/users"), Resolution::NotFound);
+        assert_eq!(router.resolve("/settings/nope"), Resolution::NotFound);
+        assert_eq!(Router::history().resolve("/"), Resolution::NotFound);
+    }
+
+    #[test]
+    fn redirects_fill_in_params_and_follow_chains() {
+        let router = app()
+            .redirect("/old", "/new")
+            .redirect("/new", "/settings")
+            .redirect("/u/:id", "/users/:id")
+            .redirect("/docs/*rest", "/files/*rest");
+        assert_eq!(
+            router.resolve("/old"),
+            Resolution::Redirect("/settings".into())
+        );
+        assert_eq!(
+            router.resolve("/u/a b"),
+            Resolution::Redirect("/users/a%20b".into())
+        );
+        assert_eq!(
+            router.resolve("/docs/a/b"),
+            Resolution::Redirect("/files/a/b".into())
+        );
+        // Redirects win over routes.
+        let router = app().redirect("/users/:id", "/");
+        assert_eq!(router.resolve("/users/1"), Resolution::Redirect("/".into()));
+    }
+
+    #[test]
+    fn redirect_loops_resolve_to_not_found() {
+        let router = app().redirect("/a", "/b").redirect("/b", "/a");
+        assert_eq!(router.resolve("/a"), Resolution::NotFound);
+        let router = app().redirect("/users/:id", "/users/:id");
+        assert_eq!(router.resolve("/users/1"), Resolution::NotFound);
+    }
+
+    #[test]
+    fn guards_allow_deny_or_redirect() {
+        let signed_in = Rc::new(Cell::new(false));
+        let session = Rc::clone(&signed_in);
+        let router = app()
+            .route("/login", |_| unreachable!())
+            .guard("/settings", move |_| match session.get() {
+                true => Guard::Allow,
+                false => Guard::Redirect("/login".into()),
+            })
+            .guard("/users/:id", |p| match p.get("id") {
+                Some("0") => Guard::Deny,
+                _ => Guard::Allow,
+            });
+
+        assert_eq!(
+            router.resolve("/settings/profile"),
+            Resolution::Redirect("/login".into())
+        );
+        signed_in.set(true);
+        assert_eq!(
+            router.resolve("/settings/profile"),
+            matched("/settings/profile", &[])
+        );
+        assert_eq!(router.resolve("/users/0"), Resolution::Denied);
+        assert_eq!(
+            router.resolve("/users/1"),
+            matched("/users/:id", &[("id", "1")])
+        );
+        // A redirect into a denied path lands there, then is denied.
+        let router = app()
+            .redirect("/me", "/users/0")
+            .guard("/users/:id", |_| Guard::Deny);
+        assert_eq!(
+            router.resolve("/me"),
+            Resolution::Redirect("/users/0".into())
+        );
+        assert_eq!(router.resolve("/users/0"), Resolution::Denied);
+    }
+
+    #[test]
+    fn guard_redirect_loops_resolve_to_not_found() {
+        let router = app().guard("/", |_| Guard::Redirect("/".into()));
+        assert_eq!(router.resolve("/"), Resolution::NotFound);
+    }
+
+    #[test]
+    fn canonicalize_strips_extra_slashes() {
+        assert_eq!(canonical_path(""), "/");
+        assert_eq!(canonical_path("//users//42/"), "/users/42");
+        let router = app().canonicalize();
+        assert_eq!(
+            router.resolve("/users/42/"),
+            Resolution::Redirect("/users/42".into())
+        );
+        assert_eq!(
+            router.resolve("//settings//profile"),
+            Resolution::Redirect("/settings/profile".into())
+        );
+        assert_eq!(
+            router.resolve("/users/42"),
+            matched("/users/:id", &[("id", "42")])
+        );
+        // Without the option, sloppy paths still match.
+        assert_eq!(
+            app().resolve("/users/42/"),
+            matched("/users/:id", &[("id", "42")])
+        );
+    }
+
+    #[test]
+    fn locations_split_into_path_query_and_hash() {
+        let location = Location::parse("/users?tab=posts&page=2#bio");
+        assert_eq!(location.path, "/users");
+        assert_eq!(location.query, "tab=posts&page=2");
+        assert_eq!(location.hash, "bio");
+        assert_eq!(Location::parse(""), Location::parse("/"));
+        assert_eq!(Location::parse("/").path, "/");
+        assert_eq!(Location::parse("about").path, "/about");
+        assert_eq!(Location::parse("/a#b?c").query, "");
+        assert_eq!(Location::parse("/a#b?c").hash, "b?c");
+    }
+
+    #[test]
+    fn locations_join_back_into_urls() {
+        for url in [
+            "/",
+            "/users?tab=posts",
+            "/users#bio",
+            "/users?tab=posts&page=2#bio",
+        ] {
+            assert_eq!(Location::parse(url).to_url(), url);
+        }
+    }
+
+    #[test]
+    fn links_point_at_the_router_mode() {
+        assert_eq!(href(RouterMode::Hash, "/about"), "#/about");
+        assert_eq!(href(RouterMode::History, "/about?tab=1"), "/about?tab=1");
+    }
+
+    #[test]
+    fn links_are_active_on_their_path_and_below() {
+        assert!(is_active("/settings", "/settings"));
+        assert!(is_active("/settings", "/settings/profile"));
+        assert!(is_active("/settings/", "/settings"));
+        assert!(!is_active("/settings", "/"));
+        assert!(!is_active("/settings", "/settingsx"));
+        assert!(!is_active("/settings/profile", "/settings"));
+        assert!(is_active("/", "/"));
+        assert!(!is_active("/", "/about"));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_values() {
+        let params = match_path("/users/:id", "/users/abc").unwrap();
+        assert_eq!(params.parse::<u64>("id"), None);
+        assert_eq!(params.parse::<String>("id").as_deref(), Some("abc"));
+    }
+
+    /// A future that stays pending until a value is put in its slot.
+    struct Gate<T>(Rc<RefCell<Option<T>>>);
+
+    impl<T> Future for Gate<T> {
+        type Output = T;
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<T> {
+            match self.0.borrow_mut().take() {
+                Some(value) => Poll::Ready(value),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    /// Starts the async view of the route matching `path`, as rendering it
+    /// in the current scope would.
+    fn enter(router: &Router, path: &str) -> InScope {
+        let Resolved::Routes(chain) = router.resolve_chain(path) else {
+            panic!("{path} matched no route");
+        };
+        let indices: Vec<usize> = chain.iter().map(|(i, _)| *i).collect();
+        let View::Async(load) = &router.route_at(&indices).view else {
+            panic!("{path} is not an async route");
+        };
+        InScope::new(load(&chain[chain.len() - 1].1))
+    }
+
+    fn poll(future: &mut InScope) -> Poll<Option<Deferred>> {
+        Pin::new(future).poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    fn no_dom<T>(_: T) -> Element {
+        unreachable!("rendering needs a DOM")
+    }
+
+    #[test]
+    fn loader_stays_pending_until_it_resolves() {
+        let slot = Rc::new(RefCell::new(None));
+        let ids = Rc::new(RefCell::new(Vec::new()));
+        let (gate, log) = (Rc::clone(&slot), Rc::clone(&ids));
+        let router = Router::history().load(
+            "/users/:id",
+            move |p| {
+                log.borrow_mut()
+                    .push(p.get("id").unwrap_or_default().to_owned());
+                Gate(Rc::clone(&gate))
+            },
+            no_dom::<String>,
+        );
+
+        let route = Scope::new();
+        let mut future = route.run(|| enter(&router, "/users/7"));
+        assert_eq!(*ids.borrow(), ["7"]);
+        assert!(poll(&mut future).is_pending());
+        assert!(poll(&mut future).is_pending());
+
+        *slot.borrow_mut() = Some("Ana".to_owned());
+        assert!(matches!(poll(&mut future), Poll::Ready(Some(_))));
+    }
+
+    #[test]
+    fn leaving_while_loading_drops_the_loader() {
+        let slot = Rc::new(RefCell::new(None));
+        let gate = Rc::clone(&slot);
+        let router = Router::history().load(
+            "/users/:id",
+            move |_| Gate(Rc::clone(&gate)),
+            no_dom::<String>,
+        );
+
+        let route = Scope::new();
+        let mut future = route.run(|| enter(&router, "/users/7"));
+        assert!(poll(&mut future).is_pending());
+        assert_eq!(Rc::strong_count(&slot), 3);
+
+        route.dispose();
+        *slot.borrow_mut() = Some("Ana".to_owned());
+        assert!(matches!(poll(&mut future), Poll::Ready(None)));
+        // The loader's future, and the gate it held, are gone.
+        assert_eq!(Rc::strong_count(&slot), 2);
+    }
+
+    #[test]
+    fn lazy_view_finishing_after_the_route_left_is_dropped() {
+        let count = use_state(0);
+        let slot = Rc::new(RefCell::new(None));
+        let runs = Rc::new(Cell::new(0));
+        let (gate, c, r) = (Rc::clone(&slot), count.clone(), Rc::clone(&runs));
+        let router = Router::history().lazy("/reports", move |_| {
+            let (gate, count, runs) = (Gate(Rc::clone(&gate)), c.clone(), Rc::clone(&r));
+            async move {
+                // Set up while loading, like a view built inside the future.
+                create_effect(move || {
+                    count.get();
+                    runs.set(runs.get() + 1);
+                });
+                gate.await;
+                Err::<Element, _>("unreachable")
+            }
+        });
+
+        let route = Scope::new();
+        let mut future = route.run(|| enter(&router, "/reports"));
+        assert!(poll(&mut future).is_pending());
+        count.set(1);
+        assert_eq!(runs.get(), 2);
+
+        route.dispose();
+        count.set(2);
+        assert_eq!(runs.get(), 2);
+        *slot.borrow_mut() = Some(());
+        assert!(matches!(poll(&mut future), Poll::Ready(None)));
+    }
+}
@@ -0,0 +1,210 @@
+//! Scroll restoration for router navigations.
+//!
+//! Once a router starts, `history.scrollRestoration` is set to `manual` and
+//! scrolling is handled here: every history entry the app visits gets an id
+//! in `history.state`, the page's scroll offset is recorded under the current
+//! id as it scrolls, and going Back/Forward to an entry restores its offset.
+//! New navigations start at the top, or at the element a `#fragment` names.
+//!
+//! The id is one field of the state object, next to whatever the app keeps
+//! there. Offsets are saved to `sessionStorage` on every navigation and when
+//! the page is hidden, so they survive reloads and pages coming back from
+//! the back/forward cache.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use js_sys::{Object, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Event, ScrollRestoration, Storage};
+
+use crate::{document, listen, router::decode, router::history};
+
+/// The `history.state` field holding an entry's id.
+const STATE_FIELD: &str = "typhoonScrollId";
+/// The `sessionStorage` key offsets are saved under.
+const STORAGE_KEY: &str = "typhoon:scroll";
+
+struct Entries {
+    current: u64,
+    offsets: HashMap<u64, (f64, f64)>,
+}
+
+thread_local! {
+    /// `None` until a router takes over scrolling.
+    static ENTRIES: RefCell<Option<Entries>> = const { RefCell::new(None) };
+}
+
+/// Takes over scroll restoration from the browser. Later calls do nothing.
+pub(crate) fn install() {
+    if ENTRIES.with(|e| e.borrow().is_some()) {
+        return;
+    }
+    let history = history();
+    history
+        .set_scroll_restoration(ScrollRestoration::Manual)
+        .ok();
+    let current = entry_id(&current_state()).unwrap_or_else(|| {
+        let id = new_id();
+        tag_current(id);
+        id
+    });
+    let offsets = session_storage()
+        .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    ENTRIES.with(|e| *e.borrow_mut() = Some(Entries { current, offsets }));
+
+    // Live as long as the page, like the location listeners.
+    let window = web_sys::window().expect("no window");
+    std::mem::forget(listen(&window, "scroll", |_: Event| save()));
+    std::mem::forget(listen(&window, "pagehide", |_: Event| {
+        save();
+        store();
+    }));
+}
+
+/// Records the scroll offset of the current entry.
+fn save() {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let offset = (
+        window.scroll_x().unwrap_or_default(),
+        window.scroll_y().unwrap_or_default(),
+    );
+    ENTRIES.with(|e| {
+        if let Some(entries) = e.borrow_mut().as_mut() {
+            entries.offsets.insert(entries.current, offset);
+        }
+    });
+}
+
+/// Saves the recorded offsets to `sessionStorage`.
+fn store() {
+    let Some(storage) = session_storage() else {
+        return;
+    };
+    ENTRIES.with(|e| {
+        if let Some(entries) = e.borrow().as_ref()
+            && let Ok(json) = serde_json::to_string(&entries.offsets)
+        {
+            storage.set_item(STORAGE_KEY, &json).ok();
+        }
+    });
+}
+
+/// The `history.state` for a new entry the router is about to push.
+pub(crate) fn push_state() -> JsValue {
+    save();
+    store();
+    let id = ENTRIES.with(|e| {
+        let mut entries = e.borrow_mut();
+        let entries = entries.as_mut()?;
+        entries.current = new_id();
+        Some(entries.current)
+    });
+    id.and_then(|id| with_id(&JsValue::NULL, id))
+        .unwrap_or(JsValue::NULL)
+}
+
+/// The `history.state` for replacing the current entry: its current state,
+/// still tagged with its id.
+pub(crate) fn replace_state() -> JsValue {
+    let state = current_state();
+    ENTRIES
+        .with(|e| e.borrow().as_ref().map(|entries| entries.current))
+        .and_then(|id| with_id(&state, id))
+        .unwrap_or(state)
+}
+
+/// After a new navigation: scrolls to the element `hash` names, or the top.
+pub(crate) fn navigated(hash: &str) {
+    if ENTRIES.with(|e| e.borrow().is_some()) {
+        scroll_to(hash);
+    }
+}
+
+/// After Back/Forward (or a followed `#fragment` link): restores the offset
+/// recorded for the entry, treating an entry without one as new.
+pub(crate) fn traversed(hash: &str) {
+    let id = entry_id(&current_state());
+    let offset = ENTRIES.with(|e| {
+        let mut entries = e.borrow_mut();
+        let entries = entries.as_mut()?;
+        match id {
+            // Already handled by `navigate`.
+            Some(id) if id == entries.current => None,
+            Some(id) => {
+                entries.current = id;
+                Some(entries.offsets.get(&id).copied())
+            }
+            None => {
+                entries.current = new_id();
+                tag_current(entries.current);
+                Some(None)
+            }
+        }
+    });
+    match offset {
+        Some(Some((x, y))) => {
+            if let Some(window) = web_sys::window() {
+                window.scroll_to_with_x_and_y(x, y);
+            }
+        }
+        Some(None) => scroll_to(hash),
+        None => {}
+    }
+}
+
+fn scroll_to(hash: &str) {
+    if !hash.is_empty()
+        && let Some(target) = document().get_element_by_id(&decode(hash))
+    {
+        target.scroll_into_view();
+    } else if let Some(window) = web_sys::window() {
+        window.scroll_to_with_x_and_y(0.0, 0.0);
+    }
+}
+
+fn current_state() -> JsValue {
+    history().state().unwrap_or(JsValue::NULL)
+}
+
+/// Stores `id` in the current entry's state.
+fn tag_current(id: u64) {
+    if let Some(state) = with_id(&current_state(), id) {
+        history().replace_state(&state, "").ok();
+    }
+}
+
+/// A copy of `state` with its entry id set to `id`, keeping the app's own
+/// fields. State that isn't an object has nowhere to put an id, so it is
+/// left alone and the entry goes without.
+fn with_id(state: &JsValue, id: u64) -> Option<JsValue> {
+    let tagged = Object::new();
+    if state.is_object() {
+        Object::assign(&tagged, state.unchecked_ref());
+    } else if !state.is_null() && !state.is_undefined() {
+        return None;
+    }
+    Reflect::set(&tagged, &STATE_FIELD.into(), &JsValue::from_f64(id as f64)).ok()?;
+    Some(tagged.into())
+}
+
+fn entry_id(state: &JsValue) -> Option<u64> {
+    if !state.is_object() {
+        return None;
+    }
+    let id = Reflect::get(state, &STATE_FIELD.into()).ok()?;
+    id.as_f64().map(|id| id as u64)
+}
+
+fn session_storage() -> Option<Storage> {
+    web_sys::window()?.session_storage().ok().flatten()
+}
+
+/// Random, so ids left in `history.state` by an earlier page load don't
+/// collide with new ones.
+fn new_id() -> u64 {
+    (js_sys::Math::random() * (1u64 << 53) as f64) as u64
+}
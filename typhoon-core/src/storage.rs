@@ -0,0 +1,920 @@
+//! Signals persisted as JSON in a key-value store.
+//!
+//! [`use_persisted`] holds the serde logic; where the JSON goes is up to a
+//! [`StorageBackend`]. [`Backend`] covers the browser's `localStorage` and
+//! `sessionStorage`, plus an in-memory map for tests and non-browser targets.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use wasm_bindgen::{JsCast, prelude::Closure};
+use web_sys::{Event, StorageEvent, Window};
+
+use crate::{Signal, error_message, listen, on_cleanup};
+
+/// Somewhere to keep persisted values, as strings under string keys.
+pub trait StorageBackend {
+    fn get_item(&self, key: &str) -> Option<String>;
+    fn set_item(&self, key: &str, value: &str) -> Result<(), String>;
+    fn remove_item(&self, key: &str);
+
+    /// Calls `changed` with the new value (`None` if removed) whenever `key`
+    /// is changed from outside the app, e.g. by another tab. Backends that
+    /// can't tell leave this as a no-op.
+    ///
+    /// Call it inside a [`Scope`](crate::Scope) to stop watching when the
+    /// scope is disposed.
+    fn watch(&self, key: &str, changed: Box<dyn Fn(Option<String>)>) {
+        let _ = (key, changed);
+    }
+}
+
+/// The built-in storage backends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// `localStorage`: shared by every tab of the origin, kept across sessions.
+    Local,
+    /// `sessionStorage`: per tab, cleared when the tab is closed.
+    Session,
+    /// A map shared by the whole app, lost on reload. Works outside the browser.
+    Memory,
+}
+
+thread_local! {
+    static MEMORY: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+impl Backend {
+    fn web_storage(self) -> Option<web_sys::Storage> {
+        match self {
+            Backend::Local => web_sys::window()?.local_storage().ok().flatten(),
+            Backend::Session => web_sys::window()?.session_storage().ok().flatten(),
+            Backend::Memory => None,
+        }
+    }
+}
+
+impl StorageBackend for Backend {
+    fn get_item(&self, key: &str) -> Option<String> {
+        match self {
+            Backend::Memory => MEMORY.with(|m| m.borrow().get(key).cloned()),
+            _ => self.web_storage()?.get_item(key).ok().flatten(),
+        }
+    }
+
+    fn set_item(&self, key: &str, value: &str) -> Result<(), String> {
+        match self {
+            Backend::Memory => {
+                MEMORY.with(|m| m.borrow_mut().insert(key.to_owned(), value.to_owned()));
+                Ok(())
+            }
+            _ => self
+                .web_storage()
+                .ok_or_else(|| format!("{self:?} storage is unavailable"))?
+                .set_item(key, value)
+                .map_err(|err| error_message(&err)),
+        }
+    }
+
+    fn remove_item(&self, key: &str) {
+        match self {
+            Backend::Memory => {
+                MEMORY.with(|m| m.borrow_mut().remove(key));
+            }
+            _ => {
+                if let Some(storage) = self.web_storage() {
+                    storage.remove_item(key).ok();
+                }
+            }
+        }
+    }
+
+    /// Web storage fires `storage` in every other tab of the origin that
+    /// shares the storage area; `Memory` has no other tabs.
+    fn watch(&self, key: &str, changed: Box<dyn Fn(Option<String>)>) {
+        let Some(storage) = self.web_storage() else {
+            return;
+        };
+        let window = web_sys::window().expect("no window");
+        let key = key.to_owned();
+        listen(&window, "storage", move |e: StorageEvent| {
+            if e.storage_area().as_ref() != Some(&storage) {
+                return;
+            }
+            match e.key() {
+                Some(k) if k == key => changed(e.new_value()),
+                Some(_) => {}
+                // `clear()`
+                None => changed(None),
+            }
+        })
+        .forget();
+    }
+}
+
+// ── Persisted signals ─────────────────────────────────────────────────────────
+
+type Migration = Box<dyn Fn(Value) -> Value>;
+
+/// Where and how [`use_persisted`] stores a value: a backend plus an
+/// optional schema version, migrations, write debouncing and error callback.
+///
+/// Any [`StorageBackend`] converts into a `Persist` with no schema version,
+/// which stores the bare JSON.
+///
+/// ```ignore
+/// // v1 stored `Todo { text, completed }`; v2 renamed `completed` to `done`.
+/// let todos = use_persisted(
+///     Persist::new(Backend::Local)
+///         .version(2)
+///         .migrate(1, |mut todos| {
+///             for todo in todos.as_array_mut().into_iter().flatten() {
+///                 todo["done"] = todo["completed"].take();
+///             }
+///             todos
+///         })
+///         .on_error(|err| web_sys::console::warn_1(&err.to_string().into())),
+///     "todos",
+///     Vec::<Todo>::new(),
+/// );
+/// ```
+pub struct Persist {
+    storage: Box<dyn StorageBackend>,
+    version: u32,
+    migrations: Vec<(u32, Migration)>,
+    debounce: i32,
+    /// What debounced writes wait on; a page `setTimeout` unless set.
+    delay: Option<Box<dyn Delay>>,
+    on_error: Option<Box<dyn Fn(PersistError)>>,
+}
+
+/// A problem [`use_persisted`] ran into instead of failing silently.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PersistError {
+    /// The stored value couldn't be read back, even after migrating. The
+    /// signal starts from its default; `raw` stays in storage until the
+    /// signal is next set.
+    Decode {
+        key: String,
+        raw: String,
+        message: String,
+    },
+    /// The value couldn't be serialised; nothing was written.
+    Encode { key: String, message: String },
+    /// The backend refused the write, e.g. because the storage quota is
+    /// used up.
+    Write { key: String, message: String },
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::Decode { key, message, .. } => {
+                write!(f, "failed to read persisted {key:?}: {message}")
+            }
+            PersistError::Encode { key, message } => {
+                write!(f, "failed to serialise persisted {key:?}: {message}")
+            }
+            PersistError::Write { key, message } => {
+                write!(f, "failed to save persisted {key:?}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PersistError {}
+
+impl Persist {
+    pub fn new<S: StorageBackend + 'static>(storage: S) -> Self {
+        Persist {
+            storage: Box::new(storage),
+            version: 0,
+            migrations: Vec::new(),
+            debounce: 0,
+            delay: None,
+            on_error: None,
+        }
+    }
+
+    /// The current schema version. From version 1 on, values are stored as
+    /// `{"v": version, "data": ...}`; bare JSON stored before versioning
+    /// counts as version 0.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Upgrades data stored at version `from` to version `from + 1`. Older
+    /// data is passed through every migration up to the current version.
+    pub fn migrate<F>(mut self, from: u32, migration: F) -> Self
+    where
+        F: Fn(Value) -> Value + 'static,
+    {
+        self.migrations.push((from, Box::new(migration)));
+        self
+    }
+
+    /// Coalesces writes: the value is saved once it has stopped changing for
+    /// `ms` milliseconds, rather than on every `.set()`. Pending writes are
+    /// flushed when the page is hidden or closed (`pagehide`) and when the
+    /// current [`Scope`](crate::Scope) is disposed.
+    ///
+    /// Handy for values bound to text inputs, which change on every key.
+    /// Without a window to set timeouts on, e.g. in a worker, values are
+    /// written straight away.
+    pub fn debounce(mut self, ms: i32) -> Self {
+        self.debounce = ms;
+        self
+    }
+
+    /// Waits on `delay` instead of a page timeout when debouncing.
+    #[cfg(test)]
+    fn delay(mut self, delay: impl Delay + 'static) -> Self {
+        self.delay = Some(Box::new(delay));
+        self
+    }
+
+    /// Called with every [`PersistError`], e.g. to log it or tell the user
+    /// their saved data couldn't be loaded or saved.
+    pub fn on_error<F>(mut self, f: F) -> Self
+    where
+        F: Fn(PersistError) + 'static,
+    {
+        self.on_error = Some(Box::new(f));
+        self
+    }
+
+    fn report(&self, error: PersistError) {
+        if let Some(on_error) = &self.on_error {
+            on_error(error);
+        }
+    }
+
+    /// Reads a stored string, migrating it to the current version.
+    fn decode<T: DeserializeOwned>(&self, raw: &str) -> Result<T, String> {
+        let stored: Value = serde_json::from_str(raw).map_err(|e| e.to_string())?;
+        let (mut version, mut data) = match stored {
+            Value::Object(mut envelope) if envelope.len() == 2 && envelope.contains_key("data") => {
+                match envelope.get("v") {
+                    Some(Value::Number(v)) => {
+                        let version = v
+                            .as_u64()
+                            .and_then(|v| u32::try_from(v).ok())
+                            .ok_or_else(|| format!("invalid stored version {v}"))?;
+                        (version, envelope.remove("data").unwrap_or_default())
+                    }
+                    _ => (0, Value::Object(envelope)),
+                }
+            }
+            bare => (0, bare),
+        };
+        if version > self.version {
+            return Err(format!(
+                "stored at version {version}, newer than the current {}",
+                self.version
+            ));
+        }
+        while version < self.version {
+            let (_, migration) = self
+                .migrations
+                .iter()
+                .find(|(from, _)| *from == version)
+                .ok_or_else(|| format!("no migration from version {version}"))?;
+            data = migration(data);
+            version += 1;
+        }
+        serde_json::from_value(data).map_err(|e| e.to_string())
+    }
+
+    /// Formats a value for storage at the current version.
+    fn encode<T: Serialize>(&self, value: &T) -> Result<String, String> {
+        let data = serde_json::to_value(value).map_err(|e| e.to_string())?;
+        let stored = match self.version {
+            0 => data,
+            v => serde_json::json!({ "v": v, "data": data }),
+        };
+        Ok(stored.to_string())
+    }
+}
+
+/// Waits out the debounce delay before a write.
+trait Delay {
+    /// Calls `fire` after `ms` milliseconds, dropping any earlier countdown.
+    fn start(&self, ms: i32, fire: Rc<dyn Fn()>);
+    fn cancel(&self);
+}
+
+/// A `setTimeout` in the page.
+struct Timeout {
+    window: Window,
+    handle: Cell<Option<i32>>,
+    /// The running timeout's callback, which must outlive it.
+    callback: RefCell<Option<Closure<dyn FnMut()>>>,
+}
+
+impl Timeout {
+    fn new(window: Window) -> Self {
+        Timeout {
+            window,
+            handle: Cell::new(None),
+            callback: RefCell::new(None),
+        }
+    }
+}
+
+impl Delay for Timeout {
+    fn start(&self, ms: i32, fire: Rc<dyn Fn()>) {
+        self.cancel();
+        let callback = Closure::<dyn FnMut()>::new(move || fire());
+        let handle = self
+            .window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                callback.as_ref().unchecked_ref(),
+                ms,
+            )
+            .expect("failed to set timeout");
+        self.handle.set(Some(handle));
+        *self.callback.borrow_mut() = Some(callback);
+    }
+
+    fn cancel(&self) {
+        if let Some(handle) = self.handle.take() {
+            self.window.clear_timeout_with_handle(handle);
+        }
+    }
+}
+
+impl Drop for Timeout {
+    fn drop(&mut self) {
+        // The timeout must not fire once its callback is gone.
+        self.cancel();
+    }
+}
+
+/// Saves a persisted signal, now or after the debounce delay.
+struct Writer<T: Clone + 'static> {
+    persist: Rc<Persist>,
+    key: Rc<str>,
+    signal: Signal<T>,
+    /// `None` writes on every change.
+    delay: Option<Box<dyn Delay>>,
+    /// Whether a write is waiting for the delay.
+    pending: Cell<bool>,
+}
+
+impl<T: Serialize + Clone + 'static> Writer<T> {
+    fn write(&self) {
+        let key = || self.key.to_string();
+        match self.persist.encode(&self.signal.get()) {
+            Ok(json) => {
+                if let Err(message) = self.persist.storage.set_item(&self.key, &json) {
+                    self.persist.report(PersistError::Write {
+                        key: key(),
+                        message,
+                    });
+                }
+            }
+            Err(message) => self.persist.report(PersistError::Encode {
+                key: key(),
+                message,
+            }),
+        }
+    }
+
+    fn schedule(self: &Rc<Self>) {
+        let Some(delay) = &self.delay else {
+            self.write();
+            return;
+        };
+        self.pending.set(true);
+        let writer = Rc::downgrade(self);
+        delay.start(
+            self.persist.debounce,
+            Rc::new(move || {
+                if let Some(writer) = writer.upgrade() {
+                    writer.flush();
+                }
+            }),
+        );
+    }
+
+    /// Writes now if a write is pending.
+    fn flush(&self) {
+        if self.pending.get() {
+            self.cancel();
+            self.write();
+        }
+    }
+
+    /// Drops the pending write, if any.
+    fn cancel(&self) {
+        if self.pending.replace(false)
+            && let Some(delay) = &self.delay
+        {
+            delay.cancel();
+        }
+    }
+}
+
+impl<S: StorageBackend + 'static> From<S> for Persist {
+    fn from(storage: S) -> Self {
+        Persist::new(storage)
+    }
+}
+
+/// Reactive signal backed by `storage`. Loads `key` on creation (falling back
+/// to `default` if it is missing or can't be read) and saves the value as
+/// JSON on every `.set()`.
+///
+/// Changes made elsewhere — another tab writing the same `localStorage`
+/// key — update the signal too, without being written back.
+///
+/// Pass a [`Persist`] instead of a bare backend to version the stored
+/// schema, debounce writes and hear about values that couldn't be read or
+/// saved.
+///
+/// ```ignore
+/// let step = use_persisted(Backend::Session, "wizard-step", 0u8);
+/// step.set(2); // saved to sessionStorage
+/// ```
+pub fn use_persisted<T>(storage: impl Into<Persist>, key: &str, default: T) -> Signal<T>
+where
+    T: Serialize + DeserializeOwned + Clone + 'static,
+{
+    let mut persist = storage.into();
+    // Without a page to set timeouts in, there's nothing to wait with:
+    // values are written on every change.
+    let window = match persist.debounce {
+        ms if ms > 0 && persist.delay.is_none() => web_sys::window(),
+        _ => None,
+    };
+    if let Some(window) = &window {
+        persist.delay = Some(Box::new(Timeout::new(window.clone())));
+    }
+    let (signal, writer) = persisted(persist, key, default);
+    if let Some(window) = window {
+        listen(&window, "pagehide", move |_: Event| writer.flush()).forget();
+    }
+    signal
+}
+
+/// [`use_persisted`] once the debounce delay (if any) is settled, returning
+/// the signal's writer too.
+fn persisted<T>(mut persist: Persist, key: &str, default: T) -> (Signal<T>, Rc<Writer<T>>)
+where
+    T: Serialize + DeserializeOwned + Clone + 'static,
+{
+    let delay = persist.delay.take().filter(|_| persist.debounce > 0);
+    let persist = Rc::new(persist);
+    let key: Rc<str> = key.into();
+
+    let read = {
+        let persist = Rc::clone(&persist);
+        let key = Rc::clone(&key);
+        move |raw: Option<String>| {
+            let Some(raw) = raw else {
+                return default.clone();
+            };
+            persist.decode(&raw).unwrap_or_else(|message| {
+                persist.report(PersistError::Decode {
+                    key: key.to_string(),
+                    raw,
+                    message,
+                });
+                default.clone()
+            })
+        }
+    };
+    let signal = Signal::new(read(persist.storage.get_item(&key)));
+
+    let writer = Rc::new(Writer {
+        persist: Rc::clone(&persist),
+        key: Rc::clone(&key),
+        signal: signal.clone(),
+        delay,
+        pending: Cell::new(false),
+    });
+
+    // Set while a change from elsewhere is applied, so it isn't echoed back.
+    let applying = Rc::new(Cell::new(false));
+
+    let remote = signal.clone();
+    let remote_applying = Rc::clone(&applying);
+    let remote_writer = Rc::clone(&writer);
+    persist.storage.watch(
+        &key,
+        Box::new(move |json| {
+            // The newer value from elsewhere wins over a pending local one.
+            remote_writer.cancel();
+            remote_applying.set(true);
+            remote.set(read(json));
+            remote_applying.set(false);
+        }),
+    );
+
+    if writer.delay.is_some() {
+        let on_dispose = Rc::clone(&writer);
+        on_cleanup(move || on_dispose.flush());
+    }
+
+    let local = Rc::clone(&writer);
+    signal
+        .subscribe(move || {
+            if !applying.get() {
+                local.schedule();
+            }
+        })
+        .forget();
+
+    (signal, writer)
+}
+
+/// Reactive signal backed by `localStorage`. Persists as JSON on every `.set()`.
+pub fn use_local_storage<T>(key: &'static str, default: T) -> Signal<T>
+where
+    T: Serialize + DeserializeOwned + Clone + 'static,
+{
+    use_persisted(Backend::Local, key, default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scope;
+
+    #[test]
+    fn values_are_saved_and_reloaded() {
+        let todos = use_persisted(Backend::Memory, "todos", Vec::<String>::new());
+        assert_eq!(todos.get(), Vec::<String>::new());
+        assert_eq!(Backend::Memory.get_item("todos"), None);
+
+        todos.set(vec!["Buy milk".into()]);
+        assert_eq!(
+            Backend::Memory.get_item("todos").as_deref(),
+            Some(r#"["Buy milk"]"#)
+        );
+        let reloaded = use_persisted(Backend::Memory, "todos", Vec::<String>::new());
+        assert_eq!(reloaded.get(), vec!["Buy milk".to_owned()]);
+    }
+
+    #[test]
+    fn unreadable_values_fall_back_to_the_default() {
+        Backend::Memory.set_item("count", "not json").unwrap();
+        assert_eq!(use_persisted(Backend::Memory, "count", 7).get(), 7);
+        Backend::Memory.set_item("count", "\"text\"").unwrap();
+        assert_eq!(use_persisted(Backend::Memory, "count", 7).get(), 7);
+    }
+
+    /// A backend that records every write.
+    #[derive(Clone, Default)]
+    struct Log(std::rc::Rc<RefCell<Vec<(String, String)>>>);
+
+    impl StorageBackend for Log {
+        fn get_item(&self, _: &str) -> Option<String> {
+            None
+        }
+        fn set_item(&self, key: &str, value: &str) -> Result<(), String> {
+            self.0.borrow_mut().push((key.to_owned(), value.to_owned()));
+            Ok(())
+        }
+        fn remove_item(&self, _: &str) {}
+    }
+
+    /// A backend whose `watch` callbacks can be fired by hand, like another
+    /// tab writing.
+    #[derive(Clone, Default)]
+    struct Shared {
+        log: Log,
+        watchers: Rc<RefCell<Vec<Watcher>>>,
+    }
+
+    type Watcher = Box<dyn Fn(Option<String>)>;
+
+    impl StorageBackend for Shared {
+        fn get_item(&self, key: &str) -> Option<String> {
+            self.log.get_item(key)
+        }
+        fn set_item(&self, key: &str, value: &str) -> Result<(), String> {
+            self.log.set_item(key, value)
+        }
+        fn remove_item(&self, _: &str) {}
+        fn watch(&self, _: &str, changed: Box<dyn Fn(Option<String>)>) {
+            self.watchers.borrow_mut().push(changed);
+        }
+    }
+
+    #[test]
+    fn outside_changes_update_the_signal_without_echo() {
+        let shared = Shared::default();
+        let count = use_persisted(shared.clone(), "count", 0);
+        let fire = |json: Option<&str>| {
+            for changed in shared.watchers.borrow().iter() {
+                changed(json.map(str::to_owned));
+            }
+        };
+
+        fire(Some("5"));
+        assert_eq!(count.get(), 5);
+        fire(None);
+        assert_eq!(count.get(), 0);
+        fire(Some("garbage"));
+        assert_eq!(count.get(), 0);
+        assert!(shared.log.0.borrow().is_empty());
+
+        count.set(6);
+        assert_eq!(*shared.log.0.borrow(), [("count".into(), "6".into())]);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+    struct Todo {
+        text: String,
+        done: bool,
+    }
+
+    fn todo(text: &str, done: bool) -> Todo {
+        Todo {
+            text: text.into(),
+            done,
+        }
+    }
+
+    /// v1 stored `{ text, completed }`; v2 renamed `completed` to `done`.
+    fn todo_schema() -> Persist {
+        Persist::new(Backend::Memory)
+            .version(2)
+            .migrate(
+                0,
+                |text| serde_json::json!({ "text": text, "completed": false }),
+            )
+            .migrate(1, |mut todo| {
+                todo["done"] = todo["completed"].take();
+                todo
+            })
+    }
+
+    #[test]
+    fn versioned_values_are_stored_in_an_envelope() {
+        let persist = todo_schema();
+        let json = persist.encode(&todo("a", true)).unwrap();
+        assert_eq!(json, r#"{"data":{"done":true,"text":"a"},"v":2}"#);
+        assert_eq!(persist.decode::<Todo>(&json), Ok(todo("a", true)));
+        assert_eq!(Persist::new(Backend::Memory).encode(&1).unwrap(), "1");
+    }
+
+    #[test]
+    fn old_versions_are_migrated() {
+        let persist = todo_schema();
+        assert_eq!(
+            persist.decode::<Todo>(r#"{"v":1,"data":{"text":"a","completed":true}}"#),
+            Ok(todo("a", true))
+        );
+        // Bare JSON from before versioning is version 0.
+        assert_eq!(persist.decode::<Todo>(r#""a""#), Ok(todo("a", false)));
+        // An object that only looks like an envelope is still version 0 data.
+        let plain = Persist::new(Backend::Memory).version(1).migrate(0, |v| v);
+        assert_eq!(
+            plain.decode::<HashMap<String, String>>(r#"{"v":"x","data":"y"}"#),
+            Ok(HashMap::from([
+                ("v".to_owned(), "x".to_owned()),
+                ("data".to_owned(), "y".to_owned())
+            ]))
+        );
+    }
+
+    #[test]
+    fn unreadable_versions_are_errors() {
+        let persist = todo_schema();
+        let newer = persist.decode::<Todo>(r#"{"v":3,"data":{}}"#).unwrap_err();
+        assert!(newer.contains("newer"), "{newer}");
+        let persist = Persist::new(Backend::Memory).version(2);
+        let missing = persist.decode::<Todo>(r#"{"v":1,"data":{}}"#).unwrap_err();
+        assert!(missing.contains("no migration from version 1"), "{missing}");
+        // Out of `u32` range: not truncated into a version that has migrations.
+        for raw in [
+            r#"{"v":-1,"data":{}}"#,
+            r#"{"v":4294967297,"data":{}}"#,
+            r#"{"v":1.5,"data":{}}"#,
+        ] {
+            let invalid = persist.decode::<Todo>(raw).unwrap_err();
+            assert!(invalid.contains("invalid stored version"), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn invalid_versions_are_reported() {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&errors);
+        let raw = r#"{"v":4294967296,"data":[1]}"#;
+        Backend::Memory.set_item("wrapped", raw).unwrap();
+        let wrapped = use_persisted(
+            Persist::new(Backend::Memory)
+                .version(1)
+                .migrate(0, |_| Value::Array(Vec::new()))
+                .on_error(move |e| log.borrow_mut().push(e)),
+            "wrapped",
+            vec![7],
+        );
+        assert_eq!(wrapped.get(), [7]);
+        let errors = errors.borrow();
+        assert_eq!(errors.len(), 1);
+        let PersistError::Decode { raw: kept, .. } = &errors[0] else {
+            panic!("expected a decode error, got {:?}", errors[0]);
+        };
+        assert_eq!(kept, raw);
+    }
+
+    #[test]
+    fn decode_errors_are_reported_and_the_data_kept() {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&errors);
+        Backend::Memory.set_item("saved", "{oops").unwrap();
+        let saved = use_persisted(
+            Persist::new(Backend::Memory).on_error(move |e| log.borrow_mut().push(e)),
+            "saved",
+            3,
+        );
+        assert_eq!(saved.get(), 3);
+        assert_eq!(Backend::Memory.get_item("saved").as_deref(), Some("{oops"));
+        let errors = errors.borrow();
+        assert_eq!(errors.len(), 1);
+        let PersistError::Decode { key, raw, .. } = &errors[0] else {
+            panic!("expected a decode error, got {:?}", errors[0]);
+        };
+        assert_eq!((key.as_str(), raw.as_str()), ("saved", "{oops"));
+    }
+
+    /// A backend that refuses every write.
+    struct Full;
+
+    impl StorageBackend for Full {
+        fn get_item(&self, _: &str) -> Option<String> {
+            None
+        }
+        fn set_item(&self, _: &str, _: &str) -> Result<(), String> {
+            Err("QuotaExceededError: The quota has been exceeded.".into())
+        }
+        fn remove_item(&self, _: &str) {}
+    }
+
+    #[test]
+    fn write_and_encode_errors_are_reported() {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&errors);
+        let draft = use_persisted(
+            Persist::new(Full).on_error(move |e| log.borrow_mut().push(e)),
+            "draft",
+            String::new(),
+        );
+        draft.set("hello".into());
+        assert_eq!(
+            *errors.borrow(),
+            [PersistError::Write {
+                key: "draft".into(),
+                message: "QuotaExceededError: The quota has been exceeded.".into()
+            }]
+        );
+
+        // JSON object keys must be strings.
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&errors);
+        let grid = use_persisted(
+            Persist::new(Backend::Memory).on_error(move |e| log.borrow_mut().push(e)),
+            "grid",
+            HashMap::<(u8, u8), bool>::new(),
+        );
+        grid.set(HashMap::from([((0, 0), true)]));
+        assert_eq!(Backend::Memory.get_item("grid"), None);
+        let errors = errors.borrow();
+        assert!(
+            matches!(&errors[..], [PersistError::Encode { key, .. }] if key == "grid"),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn custom_backends_receive_writes() {
+        let log = Log::default();
+        let flag = use_persisted(log.clone(), "flag", false);
+        flag.set(true);
+        flag.set(false);
+        assert_eq!(
+            *log.0.borrow(),
+            [
+                ("flag".into(), "true".into()),
+                ("flag".into(), "false".into())
+            ]
+        );
+    }
+
+    /// A debounce delay that only runs out when told to.
+    #[derive(Clone, Default)]
+    struct Manual(Rc<RefCell<Option<Fire>>>);
+
+    type Fire = Rc<dyn Fn()>;
+
+    impl Manual {
+        fn armed(&self) -> bool {
+            self.0.borrow().is_some()
+        }
+
+        fn run_out(&self) {
+            let fire = self.0.borrow_mut().take();
+            if let Some(fire) = fire {
+                fire();
+            }
+        }
+    }
+
+    impl Delay for Manual {
+        fn start(&self, _: i32, fire: Rc<dyn Fn()>) {
+            *self.0.borrow_mut() = Some(fire);
+        }
+        fn cancel(&self) {
+            self.0.borrow_mut().take();
+        }
+    }
+
+    fn debounced(log: &Log, delay: &Manual) -> (Signal<String>, Rc<Writer<String>>) {
+        persisted(
+            Persist::new(log.clone()).debounce(300).delay(delay.clone()),
+            "draft",
+            String::new(),
+        )
+    }
+
+    #[test]
+    fn debounced_writes_wait_for_the_value_to_settle() {
+        let (log, delay) = (Log::default(), Manual::default());
+        let draft = use_persisted(
+            Persist::new(log.clone()).debounce(300).delay(delay.clone()),
+            "draft",
+            String::new(),
+        );
+
+        draft.set("H".into());
+        draft.set("Hi".into());
+        assert!(delay.armed());
+        assert!(log.0.borrow().is_empty());
+
+        delay.run_out();
+        assert_eq!(*log.0.borrow(), [("draft".into(), r#""Hi""#.into())]);
+        // Nothing left to write.
+        delay.run_out();
+        assert_eq!(log.0.borrow().len(), 1);
+    }
+
+    #[test]
+    fn pending_writes_are_flushed_on_pagehide_and_dispose() {
+        let (log, delay) = (Log::default(), Manual::default());
+        // `pagehide` calls the writer's flush.
+        let (draft, writer) = debounced(&log, &delay);
+        draft.set("Hi".into());
+        writer.flush();
+        assert!(!delay.armed());
+        assert_eq!(*log.0.borrow(), [("draft".into(), r#""Hi""#.into())]);
+        writer.flush();
+        assert_eq!(log.0.borrow().len(), 1);
+
+        let (log, delay) = (Log::default(), Manual::default());
+        let scope = Scope::new();
+        let (draft, _) = scope.run(|| debounced(&log, &delay));
+        draft.set("Bye".into());
+        scope.dispose();
+        assert_eq!(*log.0.borrow(), [("draft".into(), r#""Bye""#.into())]);
+    }
+
+    #[test]
+    fn outside_changes_cancel_pending_writes() {
+        let (shared, delay) = (Shared::default(), Manual::default());
+        let (draft, _) = persisted(
+            Persist::new(shared.clone())
+                .debounce(300)
+                .delay(delay.clone()),
+            "draft",
+            String::new(),
+        );
+        draft.set("mine".into());
+        for changed in shared.watchers.borrow().iter() {
+            changed(Some(r#""theirs""#.into()));
+        }
+        assert!(!delay.armed());
+        delay.run_out();
+        assert_eq!(draft.get(), "theirs");
+        assert!(shared.log.0.borrow().is_empty());
+    }
+
+    #[test]
+    fn writes_are_immediate_without_debounce() {
+        let (log, delay) = (Log::default(), Manual::default());
+        let count = use_persisted(Persist::new(log.clone()).delay(delay.clone()), "clicks", 0);
+        count.set(3);
+        assert!(!delay.armed());
+        assert_eq!(*log.0.borrow(), [("clicks".into(), "3".into())]);
+    }
+}
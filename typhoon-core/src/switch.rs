@@ -0,0 +1,98 @@
+//! Conditional rendering: swap a subtree when a reactive key changes.
+
+use std::{cell::RefCell, rc::Rc};
+
+use web_sys::{DocumentFragment, Node};
+
+use crate::{Scope, create_effect, document, untrack};
+
+/// Renders `render(key())` into `parent` and swaps it whenever `key()` changes.
+///
+/// `key` is tracked like an effect; `render` is not, and only runs when the
+/// key differs from the previous one. Each rendered view gets its own
+/// [`Scope`], disposed when the view is swapped out. Views live between two
+/// empty marker nodes, so `parent` can hold other children too.
+///
+/// Returning a `DocumentFragment` from `render` inserts all of its children.
+///
+/// ```ignore
+/// switch_view(&panel, move || logged_in.get(), |yes| {
+///     if yes { tp! { p.text("Welcome back") } } else { login_form() }
+/// });
+/// ```
+pub fn switch_view<K, N, KF, RF>(parent: &Node, key: KF, render: RF)
+where
+    K: PartialEq + Clone + 'static,
+    N: Into<Node>,
+    KF: Fn() -> K + 'static,
+    RF: Fn(K) -> N + 'static,
+{
+    let start: Node = document().create_text_node("").into();
+    let end: Node = document().create_text_node("").into();
+    parent
+        .append_child(&start)
+        .expect("failed to append view anchor");
+    parent
+        .append_child(&end)
+        .expect("failed to append view anchor");
+
+    // Views outlive individual effect runs (a re-run with the same key keeps
+    // the view), so they hang off this scope rather than the effect's own.
+    let views = Scope::new();
+    let current: RefCell<Option<(K, Scope)>> = RefCell::new(None);
+
+    create_effect(move || {
+        let key = key();
+        if matches!(&*current.borrow(), Some((prev, _)) if *prev == key) {
+            return;
+        }
+        untrack(|| {
+            if let Some((_, scope)) = current.borrow_mut().take() {
+                scope.dispose();
+            }
+            clear_between(&start, &end);
+
+            let scope = views.run(Scope::new);
+            let node: Node = scope.run(|| render(key.clone())).into();
+            if let Some(parent) = end.parent_node() {
+                parent
+                    .insert_before(&node, Some(&end))
+                    .expect("failed to insert view");
+            }
+            *current.borrow_mut() = Some((key, scope));
+        });
+    });
+}
+
+/// Form of [`switch_view`] emitted by `tp!` for `if` / `match` children.
+///
+/// `view(false)` returns just the key; `view(true)` returns the key and the
+/// rendered branch. Sharing one closure lets the condition and the branches
+/// capture the same variables.
+#[doc(hidden)]
+pub fn __tp_switch<K, F>(parent: &Node, view: F)
+where
+    K: PartialEq + Clone + 'static,
+    F: Fn(bool) -> (K, Option<DocumentFragment>) + 'static,
+{
+    let view = Rc::new(view);
+    let key_view = Rc::clone(&view);
+    switch_view(
+        parent,
+        move || key_view(false).0,
+        move |_| view(true).1.expect("tp! branch rendered nothing"),
+    );
+}
+
+/// Removes every node strictly between `start` and `end`.
+fn clear_between(start: &Node, end: &Node) {
+    let Some(parent) = start.parent_node() else {
+        return;
+    };
+    while let Some(node) = start.next_sibling() {
+        if node.is_same_node(Some(end)) {
+            break;
+        }
+        parent.remove_child(&node).ok();
+    }
+}
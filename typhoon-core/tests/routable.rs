@@ -0,0 +1,98 @@
+use typhoon_core::Routable;
+
+#[derive(Clone, Debug, PartialEq, Routable)]
+enum Route {
+    #[at("/")]
+    Home,
+    #[at("/post/:id")]
+    Post { id: u64 },
+    #[at("/users/:name/posts/:post")]
+    UserPost { name: String, post: u32 },
+    #[at("/files/*path")]
+    Files { path: String },
+    #[not_found]
+    NotFound,
+}
+
+#[derive(Clone, Debug, PartialEq, Routable)]
+enum Strict {
+    #[at("/")]
+    Home,
+    #[at("/about")]
+    About,
+}
+
+fn round_trip(route: Route) {
+    let path = route.to_path();
+    assert_eq!(Route::from_path(&path), Some(route), "via {path}");
+}
+
+#[test]
+fn parses_paths() {
+    assert_eq!(Route::from_path("/"), Some(Route::Home));
+    assert_eq!(Route::from_path(""), Some(Route::Home));
+    assert_eq!(Route::from_path("/post/42"), Some(Route::Post { id: 42 }));
+    assert_eq!(Route::from_path("/post/42/"), Some(Route::Post { id: 42 }));
+    assert_eq!(
+        Route::from_path("/users/ann/posts/3"),
+        Some(Route::UserPost {
+            name: "ann".into(),
+            post: 3
+        })
+    );
+    assert_eq!(
+        Route::from_path("/files/a/b.txt"),
+        Some(Route::Files {
+            path: "a/b.txt".into()
+        })
+    );
+}
+
+#[test]
+fn unmatched_and_unparsable_paths_are_not_found() {
+    assert_eq!(Route::from_path("/nope"), Some(Route::NotFound));
+    assert_eq!(Route::from_path("/post/abc"), Some(Route::NotFound));
+    assert_eq!(Route::from_path("/post/1/2"), Some(Route::NotFound));
+    assert_eq!(Strict::from_path("/nope"), None);
+    assert_eq!(Strict::from_path("/about"), Some(Strict::About));
+}
+
+#[test]
+fn formats_paths() {
+    assert_eq!(Route::Home.to_path(), "/");
+    assert_eq!(Route::Post { id: 7 }.to_path(), "/post/7");
+    assert_eq!(
+        Route::UserPost {
+            name: "Jane Doe".into(),
+            post: 1
+        }
+        .to_path(),
+        "/users/Jane%20Doe/posts/1"
+    );
+    assert_eq!(
+        Route::Files {
+            path: "docs/read me.md".into()
+        }
+        .to_path(),
+        "/files/docs/read%20me.md"
+    );
+    assert_eq!(Route::Files { path: "".into() }.to_path(), "/files");
+    assert_eq!(Route::NotFound.to_path(), "/");
+    assert_eq!(Strict::About.to_path(), "/about");
+}
+
+#[test]
+fn round_trips() {
+    round_trip(Route::Home);
+    round_trip(Route::Post { id: u64::MAX });
+    round_trip(Route::UserPost {
+        name: "ünïcode / slash?".into(),
+        post: 0,
+    });
+    round_trip(Route::Files {
+        path: "a/b c/d%e".into(),
+    });
+    round_trip(Route::Files {
+        path: String::new(),
+    });
+}
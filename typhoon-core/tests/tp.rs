@@ -0,0 +1,39 @@
+//! `tp!` only builds DOM in the browser, so these check what it compiles.
+
+use typhoon_core::prelude::*;
+
+// No `PartialEq`: a `match` is keyed by the arm it takes.
+#[allow(dead_code)]
+#[derive(Clone)]
+enum Status {
+    Loading,
+    Error(String),
+    Ready,
+}
+
+#[allow(dead_code)]
+fn conditional_children(count: Signal<i32>, status: Signal<Status>, limit: i32) -> Element {
+    tp! {
+        div {
+            if count.get() > limit {
+                p.text(format!("{count} is over {}", limit))
+            } else if count.get() < 0 {
+                p.text(format!("{n} below zero", n = count.get()))
+            }
+            match status.get() {
+                Status::Loading => { p { "Loading…" } }
+                Status::Error(message) if !message.is_empty() => { p.text(message) }
+                Status::Error(_) | Status::Ready => {}
+            }
+            // Everything read above is still ours to use.
+            p.text(count)
+            button.onclick(move || status.set(Status::Ready)) { "Done" }
+            p.text(limit.to_string())
+        }
+    }
+}
+
+#[test]
+fn conditional_children_leave_their_values_usable() {
+    let _ = conditional_children;
+}
@@ -0,0 +1,153 @@
+//! Finds the local variables a `tp!` view closure reads, so they can be
+//! cloned into it instead of moved.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Expr, ExprPath, Ident, Lit, Macro, PatIdent, Token,
+    punctuated::Punctuated,
+    visit::{self, Visit},
+};
+
+use crate::{TpChild, TpIf, TpMatch};
+
+#[derive(Default)]
+pub struct Captures {
+    read: Vec<Ident>,
+    bound: Vec<Ident>,
+}
+
+impl Captures {
+    pub fn tp_if(&mut self, tp_if: &TpIf) {
+        for (cond, body) in &tp_if.branches {
+            self.visit_expr(cond);
+            self.children(body);
+        }
+        self.children(&tp_if.otherwise);
+    }
+
+    pub fn tp_match(&mut self, tp_match: &TpMatch) {
+        self.visit_expr(&tp_match.expr);
+        for arm in &tp_match.arms {
+            self.visit_pat(&arm.pat);
+            if let Some(guard) = &arm.guard {
+                self.visit_expr(guard);
+            }
+            self.children(&arm.body);
+        }
+    }
+
+    fn children(&mut self, children: &[TpChild]) {
+        for child in children {
+            match child {
+                TpChild::Node(node) => {
+                    for method in &node.methods {
+                        for arg in &method.args {
+                            self.visit_expr(arg);
+                        }
+                    }
+                    self.children(&node.children);
+                }
+                TpChild::Text(_) => {}
+                TpChild::Embed(expr) => self.visit_expr(expr),
+                TpChild::If(tp_if) => self.tp_if(tp_if),
+                TpChild::Match(tp_match) => self.tp_match(tp_match),
+                TpChild::For(tp_for) => {
+                    self.visit_pat(&tp_for.pat);
+                    self.visit_expr(&tp_for.expr);
+                    if let Some(key) = &tp_for.key {
+                        self.visit_expr(key);
+                    }
+                    self.children(&tp_for.body);
+                }
+            }
+        }
+    }
+
+    /// `let x = x.clone();` for every variable read and not bound inside.
+    ///
+    /// Names bound anywhere inside are skipped even where they'd refer to an
+    /// outer variable; those are moved, as before.
+    pub fn clones(&self) -> TokenStream2 {
+        let mut seen: Vec<&Ident> = Vec::new();
+        for ident in &self.read {
+            if !self.bound.contains(ident) && !seen.contains(&ident) {
+                seen.push(ident);
+            }
+        }
+        quote! {
+            #(let #seen = ::core::clone::Clone::clone(&#seen);)*
+        }
+    }
+
+    fn read(&mut self, ident: &Ident) {
+        let name = ident.to_string();
+        // Constants, statics, unit structs and variants are capitalised, and
+        // `__` names belong to the macro.
+        let local = name.starts_with(|c: char| c.is_lowercase() || c == '_')
+            && !name.starts_with("__")
+            && name != "self";
+        if local {
+            self.read.push(ident.clone());
+        }
+    }
+
+    /// Variables named inline in a format string, like `{count}`.
+    fn format_args(&mut self, format: &str) {
+        let mut rest = format;
+        while let Some(open) = rest.find('{') {
+            rest = &rest[open + 1..];
+            if let Some(escaped) = rest.strip_prefix('{') {
+                rest = escaped;
+                continue;
+            }
+            let end = rest.find([':', '}']).unwrap_or(rest.len());
+            if let Ok(ident) = syn::parse_str::<Ident>(&rest[..end]) {
+                self.read(&ident);
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Captures {
+    fn visit_expr_path(&mut self, expr: &'ast ExprPath) {
+        if expr.qself.is_none()
+            && let Some(ident) = expr.path.get_ident()
+        {
+            self.read(ident);
+        }
+    }
+
+    fn visit_pat_ident(&mut self, pat: &'ast PatIdent) {
+        self.bound.push(pat.ident.clone());
+        visit::visit_pat_ident(self, pat);
+    }
+
+    /// Looks into macros whose arguments are expressions, like `format!`;
+    /// others are skipped.
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        let Ok(args) = mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated) else {
+            return;
+        };
+        for arg in &args {
+            match arg {
+                // `name = value` arguments: `name` is only a name for
+                // `{name}` in the format string.
+                Expr::Assign(assign) => {
+                    if let Expr::Path(name) = &*assign.left
+                        && let Some(name) = name.path.get_ident()
+                    {
+                        self.bound.push(name.clone());
+                    }
+                    self.visit_expr(&assign.right);
+                }
+                Expr::Lit(lit) => {
+                    if let Lit::Str(format) = &lit.lit {
+                        self.format_args(&format.value());
+                    }
+                }
+                _ => self.visit_expr(arg),
+            }
+        }
+    }
+}
@@ -1,15 +1,21 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
+mod captures;
+mod routable;
+
+use captures::Captures;
+
 use syn::{
-    Expr, Ident, LitStr, Token, braced,
+    DeriveInput, Expr, ExprLit, Ident, Lit, LitStr, Pat, Token, braced,
     parse::{Parse, ParseStream, Result},
     parse_macro_input,
+    punctuated::Punctuated,
 };
 
 struct NodeMethod {
     name: Ident,
-    arg: Expr,
+    args: Vec<Expr>,
 }
 
 impl Parse for NodeMethod {
@@ -18,8 +24,18 @@ impl Parse for NodeMethod {
         let name: Ident = input.parse()?;
         let content;
         syn::parenthesized!(content in input);
-        let arg: Expr = content.parse()?;
-        Ok(NodeMethod { name, arg })
+        let args: Vec<Expr> = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+        // `.on("event", handler)` is the only method taking two arguments.
+        let arity = if name == "on" { 2 } else { 1 };
+        if args.len() != arity {
+            return Err(syn::Error::new(
+                name.span(),
+                format!(".{name}() takes {arity} argument(s)"),
+            ));
+        }
+        Ok(NodeMethod { name, args })
     }
 }
 
@@ -34,6 +50,140 @@ enum TpChild {
     Node(TpNode),
     Text(LitStr),
     Embed(Expr), // (expr) — embeds an Element returned by a component/function
+    If(TpIf),
+    Match(TpMatch),
+    For(Box<TpFor>),
+}
+
+mod kw {
+    syn::custom_keyword!(key);
+}
+
+/// `for pat in expr [key = expr] { children }`
+struct TpFor {
+    pat: Pat,
+    expr: Expr,
+    key: Option<Expr>,
+    body: Vec<TpChild>,
+}
+
+/// `if cond { children } [else if cond { children }]* [else { children }]`
+struct TpIf {
+    branches: Vec<(Expr, Vec<TpChild>)>,
+    otherwise: Vec<TpChild>,
+}
+
+/// `match expr { pat [if guard] => { children } ,* }`
+struct TpMatch {
+    expr: Expr,
+    arms: Vec<TpArm>,
+}
+
+struct TpArm {
+    pat: Pat,
+    guard: Option<Expr>,
+    body: Vec<TpChild>,
+}
+
+fn parse_children(input: ParseStream) -> Result<Vec<TpChild>> {
+    let mut children = Vec::new();
+    while !input.is_empty() {
+        if input.peek(syn::token::Paren) {
+            let inner;
+            syn::parenthesized!(inner in input);
+            children.push(TpChild::Embed(inner.parse()?));
+        } else if input.peek(LitStr) {
+            children.push(TpChild::Text(input.parse()?));
+        } else if input.peek(Token![if]) {
+            children.push(TpChild::If(input.parse()?));
+        } else if input.peek(Token![match]) {
+            children.push(TpChild::Match(input.parse()?));
+        } else if input.peek(Token![for]) {
+            children.push(TpChild::For(input.parse()?));
+        } else {
+            children.push(TpChild::Node(input.parse()?));
+        }
+    }
+    Ok(children)
+}
+
+fn parse_block(input: ParseStream) -> Result<Vec<TpChild>> {
+    let content;
+    braced!(content in input);
+    parse_children(&content)
+}
+
+impl Parse for TpIf {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut branches = Vec::new();
+        let mut otherwise = Vec::new();
+        loop {
+            input.parse::<Token![if]>()?;
+            let cond = Expr::parse_without_eager_brace(input)?;
+            branches.push((cond, parse_block(input)?));
+            if !input.peek(Token![else]) {
+                break;
+            }
+            input.parse::<Token![else]>()?;
+            if !input.peek(Token![if]) {
+                otherwise = parse_block(input)?;
+                break;
+            }
+        }
+        Ok(TpIf {
+            branches,
+            otherwise,
+        })
+    }
+}
+
+impl Parse for TpFor {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![for]>()?;
+        let pat = Pat::parse_multi_with_leading_vert(input)?;
+        input.parse::<Token![in]>()?;
+        let expr = Expr::parse_without_eager_brace(input)?;
+        let key = if input.peek(kw::key) && input.peek2(Token![=]) {
+            input.parse::<kw::key>()?;
+            input.parse::<Token![=]>()?;
+            Some(Expr::parse_without_eager_brace(input)?)
+        } else {
+            None
+        };
+        let body = parse_block(input)?;
+        Ok(TpFor {
+            pat,
+            expr,
+            key,
+            body,
+        })
+    }
+}
+
+impl Parse for TpMatch {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![match]>()?;
+        let expr = Expr::parse_without_eager_brace(input)?;
+        let content;
+        braced!(content in input);
+        let mut arms = Vec::new();
+        while !content.is_empty() {
+            let pat = Pat::parse_multi_with_leading_vert(&content)?;
+            let guard = if content.peek(Token![if]) {
+                content.parse::<Token![if]>()?;
+                Some(content.parse()?)
+            } else {
+                None
+            };
+            content.parse::<Token![=>]>()?;
+            let body = parse_block(&content)?;
+            if content.peek(Token![,]) {
+                content.parse::<Token![,]>()?;
+            }
+            arms.push(TpArm { pat, guard, body });
+        }
+        Ok(TpMatch { expr, arms })
+    }
 }
 
 impl Parse for TpNode {
@@ -45,22 +195,11 @@ impl Parse for TpNode {
             methods.push(input.parse::<NodeMethod>()?);
         }
 
-        let mut children = Vec::new();
-        if input.peek(syn::token::Brace) {
-            let content;
-            braced!(content in input);
-            while !content.is_empty() {
-                if content.peek(syn::token::Paren) {
-                    let inner;
-                    syn::parenthesized!(inner in content);
-                    children.push(TpChild::Embed(inner.parse()?));
-                } else if content.peek(LitStr) {
-                    children.push(TpChild::Text(content.parse()?));
-                } else {
-                    children.push(TpChild::Node(content.parse()?));
-                }
-            }
-        }
+        let children = if input.peek(syn::token::Brace) {
+            parse_block(input)?
+        } else {
+            Vec::new()
+        };
 
         Ok(TpNode {
             tag,
@@ -78,35 +217,53 @@ impl Parse for TpInput {
     }
 }
 
+/// Applies `arg` to `__el` through `apply: Fn(&Element, &str)`.
+///
+/// A `Signal<T>` stays bound: `apply` re-runs on every change. Any other
+/// `Display` value is applied once. See `typhoon_core::BindSignal`.
+fn bind_value(arg: &Expr, apply: TokenStream2) -> TokenStream2 {
+    quote! {
+        {
+            #[allow(unused_imports)]
+            use ::typhoon_core::{BindSignal as _, BindValue as _};
+            (&#arg).__bind(&__el, #apply);
+        }
+    }
+}
+
 fn generate_node(node: &TpNode) -> TokenStream2 {
     let tag = node.tag.to_string();
 
     let mut stmts = quote! {
         let __el = ::typhoon_core::create_element(#tag);
     };
+    // Emitted after the other methods so a `.class(..)` can't wipe the
+    // link's `active` class.
+    let mut links = TokenStream2::new();
 
     for method in &node.methods {
         let method_name = method.name.to_string();
-        let arg = &method.arg;
+        let arg = &method.args[0];
 
         match method_name.as_str() {
             "text" => {
-                stmts = quote! {
-                    #stmts
-                    ::typhoon_core::set_text_content(&__el, &#arg);
-                };
+                let bind = bind_value(
+                    arg,
+                    quote! { |el, v| ::typhoon_core::set_text_content(el, &v) },
+                );
+                stmts = quote! { #stmts #bind };
             }
             "class" => {
-                stmts = quote! {
-                    #stmts
-                    ::typhoon_core::set_class(&__el, #arg);
-                };
+                let bind = bind_value(arg, quote! { ::typhoon_core::set_class });
+                stmts = quote! { #stmts #bind };
             }
             "style" => {
-                stmts = quote! {
-                    #stmts
-                    ::typhoon_core::set_style(&__el, #arg);
-                };
+                let bind = bind_value(arg, quote! { ::typhoon_core::set_style });
+                stmts = quote! { #stmts #bind };
+            }
+            "value" => {
+                let bind = bind_value(arg, quote! { ::typhoon_core::set_value });
+                stmts = quote! { #stmts #bind };
             }
             "onclick" => {
                 stmts = quote! {
@@ -114,48 +271,93 @@ fn generate_node(node: &TpNode) -> TokenStream2 {
                     ::typhoon_core::set_onclick(&__el, #arg);
                 };
             }
-            "id" => {
+            "oninput" => {
                 stmts = quote! {
                     #stmts
-                    ::typhoon_core::set_attribute(&__el, "id", #arg);
+                    ::typhoon_core::set_oninput(&__el, #arg);
                 };
             }
-            "placeholder" => {
+            "onkeydown" => {
                 stmts = quote! {
                     #stmts
-                    ::typhoon_core::set_attribute(&__el, "placeholder", #arg);
+                    ::typhoon_core::set_onkeydown(&__el, #arg);
                 };
             }
-            "value" => {
-                stmts = quote! {
-                    #stmts
-                    ::typhoon_core::set_attribute(&__el, "value", &#arg);
+            "to" => {
+                links = quote! {
+                    #links
+                    ::typhoon_core::link(&__el, &(#arg));
                 };
             }
-            "oninput" => {
+            "on" => {
+                let handler = &method.args[1];
+                let event_ty = match arg {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(name),
+                        ..
+                    }) => event_type(&name.value()),
+                    _ => event_type(""),
+                };
                 stmts = quote! {
                     #stmts
-                    ::typhoon_core::set_oninput(&__el, #arg);
+                    ::typhoon_core::on::<#event_ty, _>(&__el, #arg, #handler);
                 };
             }
-            "onkeydown" => {
+            // `.on_submit(h)` / `.onsubmit(h)` — any other event, typed by name
+            name if name.starts_with("on") => {
+                let event = name.strip_prefix("on_").unwrap_or(&name[2..]);
+                let event_ty = event_type(event);
                 stmts = quote! {
                     #stmts
-                    ::typhoon_core::set_onkeydown(&__el, #arg);
+                    ::typhoon_core::on::<#event_ty, _>(&__el, #event, #arg);
                 };
             }
             _ => {
-                // Generic attribute fallback
+                // Generic attribute fallback (covers `id`, `placeholder`, `href`, …)
                 let attr_name = method_name;
-                stmts = quote! {
-                    #stmts
-                    ::typhoon_core::set_attribute(&__el, #attr_name, &#arg);
-                };
+                let bind = bind_value(
+                    arg,
+                    quote! { |el, v| ::typhoon_core::set_attribute(el, #attr_name, &v) },
+                );
+                stmts = quote! { #stmts #bind };
             }
         }
     }
 
-    for child in &node.children {
+    let children = generate_children(&node.children);
+    quote! { #stmts #links #children }
+}
+
+/// The `web_sys` type the browser dispatches for `event`; `Event` if unknown.
+fn event_type(event: &str) -> TokenStream2 {
+    let ty = match event {
+        "click" | "dblclick" | "auxclick" | "contextmenu" | "mousedown" | "mouseup"
+        | "mousemove" | "mouseover" | "mouseout" | "mouseenter" | "mouseleave" => "MouseEvent",
+        "pointerdown" | "pointerup" | "pointermove" | "pointerover" | "pointerout"
+        | "pointerenter" | "pointerleave" | "pointercancel" => "PointerEvent",
+        "keydown" | "keyup" | "keypress" => "KeyboardEvent",
+        "focus" | "blur" | "focusin" | "focusout" => "FocusEvent",
+        "input" | "beforeinput" => "InputEvent",
+        "submit" => "SubmitEvent",
+        "wheel" => "WheelEvent",
+        "drag" | "dragstart" | "dragend" | "dragenter" | "dragleave" | "dragover" | "drop" => {
+            "DragEvent"
+        }
+        "touchstart" | "touchend" | "touchmove" | "touchcancel" => "TouchEvent",
+        "animationstart" | "animationend" | "animationiteration" => "AnimationEvent",
+        "transitionstart" | "transitionend" | "transitionrun" | "transitioncancel" => {
+            "TransitionEvent"
+        }
+        _ => "Event",
+    };
+    let ty = Ident::new(ty, proc_macro2::Span::call_site());
+    quote! { ::typhoon_core::web_sys::#ty }
+}
+
+/// Appends `children` to `__el`, which may be an element or a fragment.
+fn generate_children(children: &[TpChild]) -> TokenStream2 {
+    let mut stmts = TokenStream2::new();
+    for child in children {
         match child {
             TpChild::Node(child_node) => {
                 let child_code = generate_node(child_node);
@@ -183,20 +385,171 @@ fn generate_node(node: &TpNode) -> TokenStream2 {
                     }
                 };
             }
+            TpChild::If(tp_if) => {
+                let view = generate_if(tp_if);
+                stmts = quote! {
+                    #stmts
+                    ::typhoon_core::__tp_switch(&__el, #view);
+                };
+            }
+            TpChild::Match(tp_match) => {
+                let view = generate_match(tp_match);
+                stmts = quote! {
+                    #stmts
+                    ::typhoon_core::__tp_switch(&__el, #view);
+                };
+            }
+            TpChild::For(tp_for) => {
+                let list = generate_for(tp_for);
+                stmts = quote! { #stmts #list };
+            }
         }
     }
-
     stmts
 }
 
+/// A fragment holding `children`, built only when `__render` is set.
+fn generate_fragment(children: &[TpChild]) -> TokenStream2 {
+    let children = generate_children(children);
+    quote! {
+        __render.then(|| {
+            let __el = ::typhoon_core::create_fragment();
+            #children
+            __el
+        })
+    }
+}
+
+/// The view closure for `__tp_switch`, keyed by the index of the taken branch.
+fn generate_if(tp_if: &TpIf) -> TokenStream2 {
+    let conds = tp_if.branches.iter().map(|(cond, _)| cond);
+    let bodies = tp_if.branches.iter().enumerate().map(|(i, (_, children))| {
+        let fragment = generate_fragment(children);
+        quote! { (#i, #fragment) }
+    });
+    let last = tp_if.branches.len();
+    let otherwise = generate_fragment(&tp_if.otherwise);
+    let mut captures = Captures::default();
+    captures.tp_if(tp_if);
+    let clones = captures.clones();
+    quote! {
+        {
+            #clones
+            move |__render: bool| {
+                #(if #conds { #bodies } else)* { (#last, #otherwise) }
+            }
+        }
+    }
+}
+
+/// Renders a `for` child: live for a `Signal<Vec<T>>` or a closure, once for
+/// any other iterable. See `typhoon_core::EachSignal`.
+fn generate_for(tp_for: &TpFor) -> TokenStream2 {
+    let TpFor {
+        pat,
+        expr,
+        key,
+        body,
+    } = tp_for;
+    let children = generate_children(body);
+    // Rows may render many times, so each one gets its own copy of the item.
+    let render = quote! {
+        move |__item: &_| {
+            #[allow(unused_variables)]
+            let #pat = ::core::clone::Clone::clone(__item);
+            let __el = ::typhoon_core::create_fragment();
+            #children
+            __el
+        }
+    };
+    let call = match key {
+        Some(key) => quote! {
+            keyed(
+                &__el,
+                // Bindings borrow from the item here; `to_owned` turns a
+                // key like `id: &u32` back into a value.
+                move |__item: &_| {
+                    #[allow(unused_variables)]
+                    let #pat = __item;
+                    (#key).to_owned()
+                },
+                #render,
+            )
+        },
+        None => quote! { each(&__el, #render) },
+    };
+    quote! {
+        {
+            #[allow(unused_imports)]
+            use ::typhoon_core::{EachSignal as _, EachValue as _};
+            (&(#expr)).__list().#call;
+        }
+    }
+}
+
+/// The view closure for `__tp_switch`, keyed by the index of the taken arm,
+/// so a new scrutinee that takes the same arm keeps the rendered view.
+fn generate_match(tp_match: &TpMatch) -> TokenStream2 {
+    let expr = &tp_match.expr;
+    let arms = tp_match.arms.iter().enumerate().map(|(i, arm)| {
+        let pat = &arm.pat;
+        let guard = arm.guard.as_ref().map(|g| quote! { if #g });
+        let fragment = generate_fragment(&arm.body);
+        quote! { #pat #guard => (#i, #fragment), }
+    });
+    let mut captures = Captures::default();
+    captures.tp_match(tp_match);
+    let clones = captures.clones();
+    quote! {
+        {
+            #clones
+            move |__render: bool| {
+                match #expr {
+                    #(#arms)*
+                }
+            }
+        }
+    }
+}
+
 /// Write HTML-like element trees in Rust.
 ///
+/// Passing a `Signal` to `.text`, `.class`, `.style`, `.value` or an attribute
+/// keeps it in sync with the DOM; any other value is set once.
+///
+/// `.on("event", handler)` and `.on_event(handler)` listen for any DOM event;
+/// the handler gets the matching `web_sys` event type (`MouseEvent`,
+/// `SubmitEvent`, …, or `Event` for unknown names).
+///
+/// `a.to("/about")` is a router link: clicks navigate without a reload, and
+/// the element gets the `active` class while its route is current.
+///
+/// `if` / `else` and `match` children re-render when a signal read in the
+/// condition makes a different branch or arm win, disposing the previous one.
+/// Variables they read are cloned into the view, so they must be `Clone`, and
+/// stay usable afterwards.
+///
+/// `for pat in expr [key = expr] { ... }` renders one row per item; it stays
+/// live when `expr` is a `Signal<Vec<T>>` or a closure, and rows are
+/// reconciled by key when one is given.
+///
 /// ```ignore
 /// tp! {
 ///     div.class("app") {
 ///         h1.text("Hello")
+///         p.text(count)
 ///         button.onclick(my_handler) { "Click" }
+///         a.to("/about") { "About" }
+///         form.on_submit(|e: web_sys::SubmitEvent| e.prevent_default())
 ///         (my_component())
+///         if count.get() > 10 {
+///             p { "That's a lot" }
+///         }
+///         ul {
+///             for todo in todos key = todo.id {
+///                 li.text(&todo.text)
+///             }
+///         }
 ///     }
 /// }
 /// ```
@@ -214,3 +567,26 @@ pub fn tp(input: TokenStream) -> TokenStream {
 
     expanded.into()
 }
+
+/// Derives `typhoon_core::Routable` for a route enum.
+///
+/// ```ignore
+/// #[derive(Clone, PartialEq, Routable)]
+/// enum Route {
+///     #[at("/")]
+///     Home,
+///     #[at("/post/:id")]
+///     Post { id: u64 },
+///     #[at("/files/*path")]
+///     Files { path: String },
+///     #[not_found]
+///     NotFound,
+/// }
+/// ```
+#[proc_macro_derive(Routable, attributes(at, not_found))]
+pub fn derive_routable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    routable::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
@@ -0,0 +1,239 @@
+//! `#[derive(Routable)]`: path parsing and formatting for route enums.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, Ident, LitStr, Result, Variant};
+
+/// One piece of an `#[at("...")]` pattern.
+enum Segment {
+    Literal(String),
+    /// `:name` — one segment.
+    Param(String),
+    /// `*name` — the rest of the path.
+    Rest(String),
+}
+
+struct Route<'a> {
+    variant: &'a Variant,
+    pattern: Option<LitStr>,
+    segments: Vec<Segment>,
+    not_found: bool,
+}
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Routable can only be derived for enums",
+        ));
+    };
+
+    let mut routes = Vec::new();
+    for variant in &data.variants {
+        routes.push(parse_variant(variant)?);
+    }
+    let mut not_found = routes.iter().filter(|r| r.not_found);
+    let fallback = not_found.next();
+    if let Some(extra) = not_found.next() {
+        return Err(Error::new_spanned(
+            &extra.variant.ident,
+            "only one variant can be #[not_found]",
+        ));
+    }
+
+    let parse_arms = routes
+        .iter()
+        .filter_map(|route| Some((route, route.pattern.as_ref()?)))
+        .map(|(route, pattern)| {
+            let ident = &route.variant.ident;
+            let names: Vec<&Ident> = field_names(route.variant);
+            let keys: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+            let tys = field_types(route.variant);
+            // Nested `if let`s rather than a let-chain: the expansion has to
+            // build on the caller's edition.
+            match route.variant.fields {
+                Fields::Unit => quote! {
+                    if ::typhoon_core::match_path(#pattern, __path).is_some() {
+                        return ::core::option::Option::Some(Self::#ident);
+                    }
+                },
+                _ => quote! {
+                    if let ::core::option::Option::Some(__params) =
+                        ::typhoon_core::match_path(#pattern, __path)
+                    {
+                        if let (#(::core::option::Option::Some(#names),)*) =
+                            (#(__params.parse::<#tys>(#keys),)*)
+                        {
+                            return ::core::option::Option::Some(Self::#ident { #(#names),* });
+                        }
+                    }
+                },
+            }
+        });
+    let fallback = match fallback {
+        Some(route) => {
+            let ident = &route.variant.ident;
+            quote! { ::core::option::Option::Some(Self::#ident) }
+        }
+        None => quote! { ::core::option::Option::None },
+    };
+
+    let format_arms = routes.iter().map(|route| {
+        let ident = &route.variant.ident;
+        let names = field_names(route.variant);
+        let pattern = match route.variant.fields {
+            Fields::Unit => quote! { Self::#ident },
+            _ => quote! { Self::#ident { #(#names),* } },
+        };
+        let pushes = route.segments.iter().map(|segment| match segment {
+            Segment::Literal(lit) => quote! {
+                __path.push('/');
+                __path.push_str(#lit);
+            },
+            Segment::Param(name) => {
+                let name = Ident::new(name, proc_macro2::Span::call_site());
+                quote! {
+                    __path.push('/');
+                    __path.push_str(&::typhoon_core::__encode_segment(&#name.to_string()));
+                }
+            }
+            Segment::Rest(name) => {
+                let name = Ident::new(name, proc_macro2::Span::call_site());
+                quote! {
+                    let __rest = #name.to_string();
+                    let __rest = __rest.trim_matches('/');
+                    if !__rest.is_empty() {
+                        __path.push('/');
+                        __path.push_str(&::typhoon_core::__encode_path(__rest));
+                    }
+                }
+            }
+        });
+        quote! {
+            #pattern => {
+                let mut __path = ::std::string::String::new();
+                #(#pushes)*
+                if __path.is_empty() {
+                    __path.push('/');
+                }
+                __path
+            }
+        }
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::typhoon_core::Routable for #name #ty_generics #where_clause {
+            fn from_path(__path: &str) -> ::core::option::Option<Self> {
+                #(#parse_arms)*
+                #fallback
+            }
+
+            fn to_path(&self) -> ::std::string::String {
+                match self {
+                    #(#format_arms)*
+                }
+            }
+        }
+    })
+}
+
+fn parse_variant(variant: &Variant) -> Result<Route<'_>> {
+    let mut pattern = None;
+    let mut not_found = false;
+    for attr in &variant.attrs {
+        if attr.path().is_ident("at") {
+            pattern = Some(attr.parse_args::<LitStr>()?);
+        } else if attr.path().is_ident("not_found") {
+            not_found = true;
+        }
+    }
+
+    if matches!(variant.fields, Fields::Unnamed(_)) {
+        return Err(Error::new_spanned(
+            variant,
+            "Routable variants need named fields, matching the pattern's :params",
+        ));
+    }
+    if not_found && !matches!(variant.fields, Fields::Unit) {
+        return Err(Error::new_spanned(
+            variant,
+            "the #[not_found] variant must be a unit variant",
+        ));
+    }
+    if pattern.is_none() && !not_found {
+        return Err(Error::new_spanned(
+            variant,
+            "missing #[at(\"/path\")] (or #[not_found])",
+        ));
+    }
+
+    let segments = match &pattern {
+        Some(pattern) => parse_pattern(pattern)?,
+        None => Vec::new(),
+    };
+
+    // Every capture needs a field and every field a capture.
+    let fields: Vec<String> = field_names(variant).iter().map(|f| f.to_string()).collect();
+    let captures: Vec<&str> = segments
+        .iter()
+        .filter_map(|s| match s {
+            Segment::Param(name) | Segment::Rest(name) => Some(name.as_str()),
+            Segment::Literal(_) => None,
+        })
+        .collect();
+    if let Some(missing) = captures.iter().find(|c| !fields.iter().any(|f| f == *c)) {
+        return Err(Error::new_spanned(
+            variant,
+            format!("pattern captures `{missing}` but the variant has no such field"),
+        ));
+    }
+    if let Some(extra) = fields.iter().find(|f| !captures.contains(&f.as_str())) {
+        return Err(Error::new_spanned(
+            variant,
+            format!("field `{extra}` is not captured by the pattern"),
+        ));
+    }
+
+    Ok(Route {
+        variant,
+        pattern,
+        segments,
+        not_found,
+    })
+}
+
+fn parse_pattern(pattern: &LitStr) -> Result<Vec<Segment>> {
+    let value = pattern.value();
+    let parts: Vec<&str> = value.split('/').filter(|s| !s.is_empty()).collect();
+    let mut segments = Vec::with_capacity(parts.len());
+    for (i, part) in parts.iter().enumerate() {
+        if let Some(name) = part.strip_prefix(':') {
+            segments.push(Segment::Param(name.to_owned()));
+        } else if let Some(name) = part.strip_prefix('*') {
+            if i + 1 != parts.len() {
+                return Err(Error::new_spanned(
+                    pattern,
+                    "`*rest` must be the last segment",
+                ));
+            }
+            segments.push(Segment::Rest(name.to_owned()));
+        } else {
+            segments.push(Segment::Literal((*part).to_owned()));
+        }
+    }
+    Ok(segments)
+}
+
+fn field_names(variant: &Variant) -> Vec<&Ident> {
+    variant
+        .fields
+        .iter()
+        .filter_map(|f| f.ident.as_ref())
+        .collect()
+}
+
+fn field_types(variant: &Variant) -> Vec<&syn::Type> {
+    variant.fields.iter().map(|f| &f.ty).collect()
+}